@@ -0,0 +1,88 @@
+//! Embeds every `.gitignore` file found in the `assets/gitignore`
+//! submodule into the executable, so that the templates are available
+//! regardless of the directory the binary is run from.
+//!
+//! The generated file contains a single static slice whose entries
+//! are of the form `(Flag, file_name, contents)`, and is included by
+//! the `assets` module.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+const ASSETS_DIR: &str = "assets/gitignore";
+
+/// The subdirectories of `ASSETS_DIR` holding templates, along with
+/// the `Flag` variant each corresponds to.
+const SUBDIRS: &[(Option<&str>, &str)] = &[
+    (None, "Default"),
+    (Some("community"), "Community"),
+    (Some("Global"), "Global"),
+];
+
+fn main() -> io::Result<()> {
+    println!("cargo:rerun-if-changed={ASSETS_DIR}");
+    let root = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(ASSETS_DIR);
+    if !root.is_dir() {
+        println!(
+            "cargo:warning=`{ASSETS_DIR}` was not found; no templates will be embedded. \
+             Run `git submodule update --init` to fetch them."
+        );
+    }
+    // the subdirectories are walked on their own, under their own flag
+    let skip = SUBDIRS
+        .iter()
+        .filter_map(|(prefix, _)| prefix.map(|prefix| root.join(prefix)))
+        .collect::<Vec<_>>();
+    let mut entries = vec![];
+    for (prefix, flag) in SUBDIRS {
+        let dir = match prefix {
+            Some(prefix) => root.join(prefix),
+            None => root.clone(),
+        };
+        for path in gitignore_files(&dir, &skip)? {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            entries.push((flag, name, path));
+        }
+    }
+    let mut out = String::from("static EMBEDDED: &[(Flag, &str, &str)] = &[\n");
+    for (flag, name, path) in entries {
+        out.push_str(&format!(
+            "    (Flag::{flag}, {name:?}, include_str!({:?})),\n",
+            path.display().to_string()
+        ));
+    }
+    out.push_str("];\n");
+    let dest = PathBuf::from(env::var("OUT_DIR").unwrap()).join("embedded_assets.rs");
+    fs::write(dest, out)
+}
+
+/// Returns the paths of the `.gitignore` files below `dir` (such as
+/// `community/JavaScript/Vue.gitignore`), descending into every
+/// subdirectory except hidden ones and those in `skip`, sorted so that
+/// the generated table is deterministic.
+fn gitignore_files(dir: &Path, skip: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(rd) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for de in rd {
+            let path = de?.path();
+            if path.is_dir() {
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                if !(hidden || skip.contains(&path)) {
+                    dirs.push(path);
+                }
+            } else if path.is_file() && path.extension().is_some_and(|ext| ext == "gitignore") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
@@ -1,4 +1,8 @@
-pub use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+pub use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::assets::Flag;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -27,25 +31,348 @@ impl Cli {
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
+    #[clap(flatten)]
+    Gen(GenCommand),
+    /// Prints a unified diff between the existing `.gitignore` file and
+    /// the one the given command would generate, without writing any
+    /// file. Exits with status 1 if there are differences.
+    Diff {
+        #[clap(subcommand)]
+        command: GenCommand,
+    },
+    /// Prints the contents of the `.gitignore` files with the provided
+    /// names without writing any file.
+    Show {
+        #[clap(value_parser)]
+        names: Vec<String>,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+
+        /// Page the output through `$PAGER` (or `less`).
+        #[clap(short, long, value_parser)]
+        pager: bool,
+    },
+    /// Checks that the sections of an existing `.gitignore` file match
+    /// the current contents of the `.gitignore` files they were built
+    /// from and were not edited by hand. Exits with status 1 if any
+    /// problem is found.
+    Check {
+        #[clap(flatten)]
+        target: InputOpts,
+
+        /// Expect the sections to have been generated with `--dedupe`.
+        #[clap(long, value_parser)]
+        dedupe: bool,
+    },
+    /// Tests whether the given paths would be ignored according to the
+    /// `.gitignore` file of the target directory (or the `.gitignore`
+    /// files with the given names) and those nested below it, showing
+    /// the deciding rule. Exits with status 1 if any path is ignored.
+    Test {
+        #[clap(value_parser, required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Test against the `.gitignore` files with the given
+        /// (comma-separated) names instead of the existing file.
+        #[clap(
+            long = "with",
+            value_parser,
+            value_delimiter = ',',
+            value_name = "NAMES"
+        )]
+        names: Vec<String>,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+
+        #[clap(flatten)]
+        target: InputOpts,
+    },
+    /// Shows every rule matching the given path or one of its parent
+    /// directories, in evaluation order, along with the `.gitignore`
+    /// file and line it comes from and which rule decides whether the
+    /// path is ignored.
+    Explain {
+        #[clap(value_parser)]
+        path: PathBuf,
+
+        /// Explain using the `.gitignore` files with the given
+        /// (comma-separated) names instead of the existing file.
+        #[clap(
+            long = "with",
+            value_parser,
+            value_delimiter = ',',
+            value_name = "NAMES"
+        )]
+        names: Vec<String>,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+
+        #[clap(flatten)]
+        target: InputOpts,
+    },
+    /// Installs the `.gitignore` files with the provided names (usually
+    /// from `Global/`, e.g., `macOS` or `JetBrains`) into the global
+    /// excludes file of git, i.e., `core.excludesFile` or else
+    /// `$XDG_CONFIG_HOME/git/ignore`. Sections already present are
+    /// updated in place, and those of other `.gitignore` files are
+    /// kept. Without names, only the sections already present are
+    /// updated.
+    Global {
+        #[clap(value_parser)]
+        names: Vec<String>,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+
+        /// Print the generated content to stdout (and a summary of what
+        /// would be written to stderr) without touching the filesystem.
+        #[clap(long, visible_alias = "stdout", value_parser)]
+        dry_run: bool,
+
+        /// Print a unified diff between the existing global excludes
+        /// file and the generated content instead of writing it,
+        /// exiting with status 1 if there are differences.
+        #[clap(long, value_parser)]
+        diff: bool,
+    },
+    /// Reports problems in a `.gitignore` file: duplicate rules, rules
+    /// that never apply due to a later broader rule, negations that can
+    /// never take effect, trailing whitespace and invalid patterns.
+    Lint {
+        /// The file to check, defaults to `.gitignore` in the current
+        /// directory.
+        #[clap(value_parser)]
+        file: Option<PathBuf>,
+
+        /// Check the combination of the `.gitignore` files with the
+        /// given (comma-separated) names instead of a file.
+        #[clap(
+            long = "with",
+            value_parser,
+            value_delimiter = ',',
+            value_name = "NAMES",
+            conflicts_with = "file"
+        )]
+        names: Vec<String>,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+    },
+    /// Lists the names of all available `.gitignore` files, grouped by
+    /// where they come from.
+    List {
+        /// Only list `.gitignore` files of the given kind.
+        #[clap(short, long, value_enum)]
+        flag: Option<FlagArg>,
+
+        /// Only list `.gitignore` files whose name contains the given
+        /// substring (ignoring case).
+        #[clap(short, long, value_parser, value_name = "SUBSTR")]
+        grep: Option<String>,
+
+        /// Show the search terms associated with each `.gitignore` file.
+        #[clap(short, long, value_parser)]
+        terms: bool,
+    },
+}
+
+/// The commands generating `.gitignore` content, which may also be run
+/// through `diff`.
+#[derive(Debug, Subcommand)]
+pub enum GenCommand {
     /// Adds to the existing `.gitignore` file the results of the
     /// search containing the provided search terms and/or names.
+    /// Sections already present in the file are left untouched, and
+    /// the file is neither backed up nor overwritten.
     Add {
         #[clap(short, long, value_parser)]
         terms: Vec<String>,
 
         #[clap(value_parser)]
         names: Vec<String>,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+
+        #[clap(flatten)]
+        opts: GenOpts,
     },
     /// Use provided search terms to determine which `.gitignore` file(s)
     /// will be sourced.
     Terms {
         #[clap(value_parser)]
         terms: Vec<String>,
+
+        /// Remove the sections of other `.gitignore` files from an
+        /// existing output file instead of keeping them.
+        #[clap(long, value_parser)]
+        prune: bool,
+
+        #[clap(flatten)]
+        opts: GenOpts,
     },
     /// Use provided names to determine which `.gitignore` file(s)
     /// will be sourced.
     For {
         #[clap(value_parser)]
         names: Vec<String>,
+
+        /// Remove the sections of other `.gitignore` files from an
+        /// existing output file instead of keeping them.
+        #[clap(long, value_parser)]
+        prune: bool,
+
+        #[clap(flatten)]
+        name_opts: NameOpts,
+
+        #[clap(flatten)]
+        opts: GenOpts,
+    },
+    /// Regenerates the sections of an existing `.gitignore` file from
+    /// the current contents of the `.gitignore` files they were built
+    /// from, preserving everything outside of those sections.
+    Update {
+        #[clap(flatten)]
+        opts: GenOpts,
     },
 }
+
+impl GenCommand {
+    pub fn opts(&self) -> &GenOpts {
+        match self {
+            GenCommand::Add { opts, .. }
+            | GenCommand::Terms { opts, .. }
+            | GenCommand::For { opts, .. }
+            | GenCommand::Update { opts } => opts,
+        }
+    }
+}
+
+/// Options shared by the commands generating `.gitignore` content.
+#[derive(Clone, Debug, Default, Args)]
+pub struct GenOpts {
+    /// Print the generated content to stdout (and a summary of what
+    /// would be written to stderr) without touching the filesystem.
+    #[clap(long, visible_alias = "stdout", value_parser)]
+    pub dry_run: bool,
+
+    #[clap(flatten)]
+    pub target: TargetOpts,
+
+    /// Print a unified diff between the existing output file and the
+    /// generated content instead of writing it, exiting with status 1
+    /// if there are differences.
+    #[clap(long, value_parser)]
+    pub diff: bool,
+
+    /// Emit each rule only once, noting which other `.gitignore` files
+    /// contain it as well.
+    #[clap(long, value_parser)]
+    pub dedupe: bool,
+
+    /// Diff each generated section separately rather than the whole
+    /// file (implies `--diff`).
+    #[clap(long, value_parser)]
+    pub by_section: bool,
+
+    /// Warn about files tracked by the git repository containing the
+    /// output file that the generated content would ignore.
+    #[clap(long, value_parser)]
+    pub check_tracked: bool,
+
+    /// List the existing files and directories in the output directory
+    /// that the generated content would ignore, with counts per rule
+    /// and per `.gitignore` file.
+    #[clap(long, value_parser)]
+    pub preview_ignored: bool,
+}
+
+impl GenOpts {
+    pub fn wants_diff(&self) -> bool {
+        self.diff || self.by_section
+    }
+}
+
+/// Options describing the location of the output file.
+#[derive(Clone, Debug, Default, Args)]
+pub struct TargetOpts {
+    /// The directory to write the output file to, which must exist
+    /// (defaults to the current working directory).
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub dir: Option<PathBuf>,
+
+    /// The name of the output file, relative to the output directory
+    /// (defaults to `.gitignore`).
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Use the `info/exclude` file of the git repository containing the
+    /// output directory instead, for rules that shouldn't be committed
+    /// (e.g., for personal editors or operating systems).
+    #[clap(long, value_parser, conflicts_with = "output")]
+    pub exclude: bool,
+}
+
+/// Options describing the location of an existing file to read, as
+/// written by the commands generating `.gitignore` content.
+#[derive(Clone, Debug, Default, Args)]
+pub struct InputOpts {
+    /// The directory containing the file to read (defaults to the
+    /// current working directory).
+    #[clap(long, value_parser, value_name = "PATH")]
+    pub dir: Option<PathBuf>,
+
+    /// The name of the file to read, relative to that directory
+    /// (defaults to `.gitignore`).
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+impl From<&InputOpts> for TargetOpts {
+    fn from(opts: &InputOpts) -> Self {
+        TargetOpts {
+            dir: opts.dir.clone(),
+            output: opts.output.clone(),
+            exclude: false,
+        }
+    }
+}
+
+/// Options controlling how names are resolved to `.gitignore` files.
+#[derive(Debug, Default, Args)]
+pub struct NameOpts {
+    /// When a name matches no `.gitignore` file exactly, use the best
+    /// approximate match instead (if it is close enough).
+    #[clap(long, value_parser)]
+    pub fuzzy: bool,
+
+    /// Abort if any name does not resolve to exactly one `.gitignore`
+    /// file.
+    #[clap(long, value_parser)]
+    pub strict: bool,
+}
+
+/// Command line counterpart of `Flag`, which (in the case of custom
+/// templates) carries data that can't be provided as an argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FlagArg {
+    Default,
+    Community,
+    Global,
+    Custom,
+}
+
+impl FlagArg {
+    pub fn matches(&self, flag: &Flag) -> bool {
+        matches!(
+            (self, flag),
+            (FlagArg::Default, Flag::Default)
+                | (FlagArg::Community, Flag::Community)
+                | (FlagArg::Global, Flag::Global)
+                | (FlagArg::Custom, Flag::Custom(_))
+        )
+    }
+}
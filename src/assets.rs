@@ -1,30 +1,50 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
 };
 
-use crate::data::{self, Data, Str, Strs};
+use crate::{
+    data::{self, Data, Str, Strs},
+    fuzzy,
+};
 
 use super::term::Term;
 
-const ASSETS_DIR: &'static str = "assets/gitignore";
+/// The location of the `github/gitignore` submodule the templates are
+/// embedded from at build time (see `build.rs`). Only used to describe
+/// where a template originally came from.
+const ASSETS_DIR: &str = "assets/gitignore";
+
+// Provides `EMBEDDED: &[(Flag, &str, &str)]`, holding the flag, file
+// name and contents of every template found under `ASSETS_DIR`.
+include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Describes where a `.gitignore` template comes from: one of the
+/// directories of the upstream `github/gitignore` repository, or a
+/// user template directory (see `AssetSource`).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Flag {
     Default,
     Community,
     Global,
+    /// A template from the given user template directory, which is
+    /// relative to the project root for project-local templates (see
+    /// `AssetSource::Local`).
+    Custom(PathBuf),
 }
 
 impl Flag {
     pub fn prefix(&self) -> Option<&str> {
         match self {
-            Flag::Default => None,
+            Flag::Default | Flag::Custom(_) => None,
             Flag::Community => Some("community"),
             Flag::Global => Some("Global"),
         }
     }
     pub fn as_path(&self) -> PathBuf {
+        if let Flag::Custom(dir) = self {
+            return dir.clone();
+        }
         let p: &Path = ASSETS_DIR.as_ref();
         if let Some(prefix) = self.prefix() {
             p.join(prefix)
@@ -32,6 +52,55 @@ impl Flag {
             p.to_path_buf()
         }
     }
+    /// Parses a flag as displayed, e.g., in the header of a managed
+    /// section, expanding a leading `~` of a custom directory.
+    pub fn parse(s: &str) -> Option<Flag> {
+        match s {
+            "default" => Some(Flag::Default),
+            "community" => Some(Flag::Community),
+            "Global" => Some(Flag::Global),
+            _ => {
+                let dir = s.strip_prefix("custom: ")?;
+                let dir = match dir.strip_prefix("~/") {
+                    Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+                    None => PathBuf::from(dir),
+                };
+                Some(Flag::Custom(dir))
+            }
+        }
+    }
+    /// Returns the short name of this flag, omitting the directory of
+    /// `Flag::Custom`.
+    pub fn label(&self) -> &str {
+        match self {
+            Flag::Default => "default",
+            Flag::Community => "community",
+            Flag::Global => "Global",
+            Flag::Custom(_) => "custom",
+        }
+    }
+}
+
+/// Displays the label of the flag, followed by the template directory
+/// for `Flag::Custom`, with the home directory abbreviated as `~`. As
+/// project-local directories are relative to the project root, the
+/// result doesn't depend on the working directory, nor on where the
+/// project happens to be checked out (see `Flag::parse`).
+impl std::fmt::Display for Flag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Flag::Custom(dir) => {
+                let home = std::env::var_os("HOME").map(PathBuf::from);
+                match home.as_ref().and_then(|home| dir.strip_prefix(home).ok()) {
+                    Some(rest) if dir.is_absolute() => {
+                        write!(f, "custom: ~/{}", rest.display())
+                    }
+                    _ => write!(f, "custom: {}", dir.display()),
+                }
+            }
+            _ => write!(f, "{}", self.label()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -82,6 +151,15 @@ impl Asset {
     pub fn has_term(&self, term: &Term) -> bool {
         self.terms.contains(term)
     }
+    /// Like `has_term`, but compares the interned strings without
+    /// regard to ASCII case, so that `cargo` matches `Cargo`.
+    pub fn has_term_ignore_case(&self, term: &Term) -> bool {
+        self.has_term(term)
+            || self
+                .terms
+                .iter()
+                .any(|t| t.as_str().eq_ignore_ascii_case(term.as_str()))
+    }
     pub fn contents(&self) -> std::io::Result<String> {
         self.git_ignore.contents()
     }
@@ -89,7 +167,12 @@ impl Asset {
 
 impl std::fmt::Display for Asset {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.git_ignore())?;
+        write!(
+            f,
+            "{} ({})",
+            self.git_ignore(),
+            self.git_ignore().get_flag()
+        )?;
         match self.terms() {
             [] => (),
             [a, bs @ ..] => {
@@ -107,6 +190,16 @@ impl std::fmt::Display for Asset {
 pub struct GitIgnore {
     name: String,
     flag: Flag,
+    contents: Contents,
+}
+
+/// Where the contents of a `GitIgnore` are read from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Contents {
+    /// Embedded into the binary at build time.
+    Embedded(&'static str),
+    /// Read from the file at the given path on demand.
+    File(PathBuf),
 }
 
 impl std::fmt::Display for GitIgnore {
@@ -119,14 +212,20 @@ impl GitIgnore {
     pub fn get_name(&self) -> &str {
         self.name.as_str()
     }
-    pub fn get_flag(&self) -> Flag {
-        self.flag
+    pub fn get_flag(&self) -> &Flag {
+        &self.flag
     }
     pub fn get_filepath(&self) -> PathBuf {
-        self.flag.as_path().join(self.get_name())
+        match &self.contents {
+            Contents::Embedded(_) => self.flag.as_path().join(self.get_name()),
+            Contents::File(path) => path.clone(),
+        }
     }
     pub fn contents(&self) -> std::io::Result<String> {
-        std::fs::read_to_string(self.get_filepath())
+        match &self.contents {
+            Contents::Embedded(contents) => Ok(contents.to_string()),
+            Contents::File(path) => std::fs::read_to_string(path),
+        }
     }
 }
 
@@ -140,57 +239,215 @@ pub fn is_gitignore_file(p: impl AsRef<Path>) -> bool {
     )
 }
 
-/// Walks the static `assets` directory, collects the `.gitignore`
-/// asset file metadata (whose list of terms contains only the
-/// term corresponding to the file name without the extension, but
-/// may later be populated) and returns the assets in an `AssetDB`.
-pub fn walk_assets() -> AssetDB {
-    use std::fs;
+/// Collects the `.gitignore` files embedded into the binary at build
+/// time (whose list of terms contains only the term corresponding to
+/// the file name without the extension, but may later be populated)
+/// and returns the assets in an `AssetDB`.
+pub fn embedded_assets() -> AssetDB {
     let mut assets = HashMap::new();
-    let mut paths = vec![Flag::Default, Flag::Community, Flag::Global]
-        .into_iter()
-        .map(|flag| {
-            let path = flag.as_path();
-            (path, flag)
-        })
-        .collect::<Vec<_>>();
-    while let Some((p, flag)) = paths.pop() {
-        for rd in fs::read_dir(p) {
-            for de in rd.into_iter().flatten() {
-                let path = de.path();
-                if path.is_file() && is_gitignore_file(&path) {
-                    let name = de.file_name().into_string().unwrap();
-                    let asset_name =
-                        AssetName(name.trim_end_matches(".gitignore").to_string(), flag);
-                    let git_ignore = GitIgnore { name, flag };
-                    let asset = Asset {
-                        terms: vec![asset_name.as_term()],
-                        git_ignore,
-                    };
-                    assets.insert(asset_name, asset);
-                }
+    for (flag, name, contents) in EMBEDDED {
+        let asset_name = AssetName(
+            name.trim_end_matches(".gitignore").to_string(),
+            flag.clone(),
+        );
+        let git_ignore = GitIgnore {
+            name: name.to_string(),
+            flag: flag.clone(),
+            contents: Contents::Embedded(contents),
+        };
+        let asset = Asset {
+            terms: vec![asset_name.as_term()],
+            git_ignore,
+        };
+        assets.insert(asset_name, asset);
+    }
+    AssetDB { assets }
+}
+
+/// Collects the `.gitignore` files directly contained in the given
+/// directory and returns them as assets flagged with `Flag::Custom` in
+/// an `AssetDB`. A missing or unreadable directory results in an empty
+/// `AssetDB`.
+pub fn dir_assets(dir: impl AsRef<Path>) -> AssetDB {
+    flagged_dir_assets(&dir, Flag::Custom(dir.as_ref().to_path_buf()))
+}
+
+/// Like `dir_assets`, but flags the assets with the given flag.
+fn flagged_dir_assets(dir: impl AsRef<Path>, flag: Flag) -> AssetDB {
+    let mut assets = HashMap::new();
+    if let Ok(rd) = std::fs::read_dir(dir) {
+        for de in rd.flatten() {
+            let path = de.path();
+            if !(path.is_file() && is_gitignore_file(&path)) {
+                continue;
+            }
+            if let Ok(name) = de.file_name().into_string() {
+                let asset_name = AssetName(
+                    name.trim_end_matches(".gitignore").to_string(),
+                    flag.clone(),
+                );
+                let git_ignore = GitIgnore {
+                    name,
+                    flag: flag.clone(),
+                    contents: Contents::File(path),
+                };
+                let asset = Asset {
+                    terms: vec![asset_name.as_term()],
+                    git_ignore,
+                };
+                assets.insert(asset_name, asset);
             }
         }
     }
     AssetDB { assets }
 }
 
+/// A place from which templates are collected when assembling an
+/// `AssetDB` via `AssetDB::from_sources`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssetSource {
+    /// The templates embedded into the binary at build time.
+    Embedded,
+    /// A directory of `*.gitignore` files.
+    Dir(PathBuf),
+    /// The project-local template directory under the given project
+    /// root, whose templates are flagged relative to the root.
+    Local(PathBuf),
+}
+
+impl AssetSource {
+    /// The directory, relative to an XDG data directory, holding
+    /// system-wide or user templates.
+    pub const DATA_SUBDIR: &'static str = "ignore_me/templates";
+    /// The directory, relative to the project root, holding
+    /// project-local templates.
+    pub const LOCAL_SUBDIR: &'static str = ".ignore_me/templates";
+
+    /// The system-wide template directories, i.e., `ignore_me/templates`
+    /// under each directory in `$XDG_DATA_DIRS` (defaulting to
+    /// `/usr/local/share:/usr/share`). Since the XDG specification
+    /// lists these in order of decreasing importance, they are
+    /// returned in reverse so that more important directories come
+    /// later.
+    pub fn system() -> Vec<Self> {
+        let dirs = std::env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+        dirs.split(':')
+            .filter(|dir| Path::new(dir).is_absolute())
+            .rev()
+            .map(|dir| AssetSource::Dir(Path::new(dir).join(Self::DATA_SUBDIR)))
+            .collect()
+    }
+
+    /// The user template directory, i.e., `ignore_me/templates` under
+    /// `$XDG_DATA_HOME` (defaulting to `~/.local/share`), if it can
+    /// be determined.
+    pub fn user() -> Option<Self> {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".local").join("share"))
+            })?;
+        Some(AssetSource::Dir(data_home.join(Self::DATA_SUBDIR)))
+    }
+
+    /// The project-local template directory `.ignore_me/templates`
+    /// under the given project root.
+    pub fn local(root: impl AsRef<Path>) -> Self {
+        AssetSource::Local(root.as_ref().to_path_buf())
+    }
+
+    /// Returns, in order of increasing precedence, the built-in
+    /// templates followed by the system-wide, user and project-local
+    /// template directories for the project rooted at `root`.
+    pub fn defaults(root: impl AsRef<Path>) -> Vec<Self> {
+        let mut sources = vec![AssetSource::Embedded];
+        sources.extend(Self::system());
+        sources.extend(Self::user());
+        sources.push(Self::local(root));
+        sources
+    }
+
+    /// Collects the templates provided by this source.
+    pub fn collect(&self) -> AssetDB {
+        match self {
+            AssetSource::Embedded => embedded_assets(),
+            AssetSource::Dir(dir) => dir_assets(dir),
+            AssetSource::Local(root) => flagged_dir_assets(
+                root.join(Self::LOCAL_SUBDIR),
+                Flag::Custom(PathBuf::from(Self::LOCAL_SUBDIR)),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for AssetSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetSource::Embedded => write!(f, "<built-in>"),
+            AssetSource::Dir(dir) => write!(f, "{}", dir.display()),
+            AssetSource::Local(root) => {
+                write!(f, "{}", root.join(Self::LOCAL_SUBDIR).display())
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct AssetDB {
     assets: HashMap<AssetName, Asset>,
 }
 
+/// Assembles the assets from the default sources (see
+/// `AssetSource::defaults`), using the current working directory as
+/// the project root.
 impl Default for AssetDB {
     fn default() -> Self {
-        walk_assets()
+        let sources = match std::env::current_dir() {
+            Ok(cwd) => AssetSource::defaults(cwd),
+            Err(_) => vec![AssetSource::Embedded],
+        };
+        Self::from_sources(&sources)
     }
 }
 
 impl AssetDB {
     #![allow(unused)]
+    /// The weight of the term score in the score of suggestions matched
+    /// through a term rather than the name of an asset.
+    const TERM_WEIGHT: f64 = 0.9;
     pub fn new() -> Self {
         Self::default()
     }
+    /// Assembles the assets provided by each of the given sources,
+    /// with assets from later sources overriding those of the same
+    /// name from earlier ones (see `AssetDB::overlay`).
+    pub fn from_sources(sources: &[AssetSource]) -> Self {
+        let mut this = AssetDB {
+            assets: HashMap::new(),
+        };
+        for source in sources {
+            this.overlay(source.collect());
+        }
+        this
+    }
+    /// Adds every asset in `other`, replacing any existing asset whose
+    /// name matches (ignoring ASCII case) regardless of its flag. Assets
+    /// of `other` sharing a name are all kept.
+    pub fn overlay(&mut self, other: AssetDB) {
+        let names = other
+            .assets
+            .keys()
+            .map(|asset_name| asset_name.as_str().to_ascii_lowercase())
+            .collect::<HashSet<_>>();
+        self.assets
+            .retain(|existing, _| !names.contains(&existing.as_str().to_ascii_lowercase()));
+        self.assets.extend(other.assets);
+    }
     pub fn new_decorated() -> Self {
         Self::new().decorated()
     }
@@ -219,7 +476,7 @@ impl AssetDB {
     pub fn get_by_name(&self, name: impl AsRef<str>) -> Option<&Asset> {
         let name = name.as_ref();
         self.assets.iter().find_map(|(asset_name, asset)| {
-            if asset_name.as_str().eq_ignore_ascii_case(&name) {
+            if asset_name.as_str().eq_ignore_ascii_case(name) {
                 Some(asset)
             } else {
                 None
@@ -229,7 +486,7 @@ impl AssetDB {
     pub fn get_mut_by_name(&mut self, name: impl AsRef<str>) -> Option<&mut Asset> {
         let name = name.as_ref();
         self.assets.iter_mut().find_map(|(asset_name, asset)| {
-            if asset_name.as_str().eq_ignore_ascii_case(&name) {
+            if asset_name.as_str().eq_ignore_ascii_case(name) {
                 Some(asset)
             } else {
                 None
@@ -239,7 +496,7 @@ impl AssetDB {
     pub fn filter_by_name<'a>(
         &'a self,
         name: impl AsRef<str> + 'a,
-    ) -> impl Iterator<Item = &'a Asset> + '_ {
+    ) -> impl Iterator<Item = &'a Asset> + 'a {
         self.assets.iter().filter_map(move |(asset_name, asset)| {
             if asset_name.as_str().eq_ignore_ascii_case(name.as_ref()) {
                 Some(asset)
@@ -251,7 +508,7 @@ impl AssetDB {
     pub fn filter_mut_by_name<'a>(
         &'a mut self,
         name: impl AsRef<str> + 'a,
-    ) -> impl Iterator<Item = &'a mut Asset> + '_ {
+    ) -> impl Iterator<Item = &'a mut Asset> + 'a {
         self.assets
             .iter_mut()
             .filter_map(move |(asset_name, asset)| {
@@ -265,7 +522,7 @@ impl AssetDB {
     pub fn get_by_names<'a, S>(
         &'a self,
         names: impl IntoIterator<Item = S> + 'a,
-    ) -> impl Iterator<Item = &'a Asset> + '_
+    ) -> impl Iterator<Item = &'a Asset> + 'a
     where
         S: AsRef<str> + 'a,
     {
@@ -274,37 +531,128 @@ impl AssetDB {
             .flat_map(|s| self.get_by_name(s).into_iter())
     }
 
+    /// Returns an iterator over all assets containing at least one of
+    /// the given terms, with terms compared case-insensitively.
     pub fn filter_by_terms<'a>(
         &'a self,
         terms: &'a [Term],
-    ) -> impl Iterator<Item = &'a Asset> + '_ {
-        self.assets.iter().filter_map(|(asset_name, asset)| {
-            if terms
-                .iter()
-                .chain(std::iter::once(&asset_name.as_term()))
-                .any(|term| asset.has_term(term))
-            {
-                Some(asset)
-            } else {
-                None
-            }
-        })
+    ) -> impl Iterator<Item = &'a Asset> + 'a {
+        self.assets
+            .values()
+            .filter(|asset| terms.iter().any(|term| asset.has_term_ignore_case(term)))
     }
     pub fn filter_mut_by_terms<'a>(
         &'a mut self,
         terms: &'a [Term],
-    ) -> impl Iterator<Item = &'a mut Asset> + '_ {
-        self.assets.iter_mut().filter_map(|(asset_name, asset)| {
-            if terms
+    ) -> impl Iterator<Item = &'a mut Asset> + 'a {
+        self.assets
+            .values_mut()
+            .filter(|asset| terms.iter().any(|term| asset.has_term_ignore_case(term)))
+    }
+    /// Ranks the assets whose names (or terms) approximately match
+    /// `name` (see `fuzzy::score`), returning at most `limit`
+    /// suggestions scoring at least `fuzzy::MIN_SCORE`, best first.
+    ///
+    /// Terms are often shared by several assets, so the score of a
+    /// match through a term is mostly made up of how well the term
+    /// matches, with the remainder made up of how well the name of the
+    /// asset matches (e.g., `nodejs` favors `Node` over `Vue`, even
+    /// though both have the term `nodeJS`). The database should thus be
+    /// decorated for terms to be of any use.
+    pub fn suggest(&self, name: impl AsRef<str>, limit: usize) -> Vec<Suggestion<'_>> {
+        let name = name.as_ref();
+        let mut suggestions = self
+            .assets
+            .iter()
+            .filter_map(|(asset_name, asset)| {
+                let name_score = fuzzy::score(name, asset_name.as_str());
+                let (score, term) = asset
+                    .terms_iter()
+                    .filter(|term| *asset_name != term.as_str())
+                    .map(|term| {
+                        let score = fuzzy::score(name, term) * Self::TERM_WEIGHT
+                            + name_score * (1.0 - Self::TERM_WEIGHT);
+                        (score, Some(*term))
+                    })
+                    .fold((name_score, None), |best, curr| {
+                        if curr.0 > best.0 {
+                            curr
+                        } else {
+                            best
+                        }
+                    });
+                (score >= fuzzy::MIN_SCORE).then_some(Suggestion {
+                    asset_name,
+                    asset,
+                    score,
+                    term,
+                })
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.asset_name.as_str().cmp(b.asset_name.as_str()))
+        });
+        suggestions.truncate(limit);
+        suggestions
+    }
+    /// Returns the asset whose `.gitignore` file has the given file
+    /// name (e.g., `Rust.gitignore`, ignoring ASCII case), preferring
+    /// the one with the given flag if several do.
+    pub fn get_by_file_name(&self, file_name: &str, flag: Option<&Flag>) -> Option<&Asset> {
+        let mut assets = self
+            .assets
+            .values()
+            .filter(|asset| {
+                asset
+                    .git_ignore()
+                    .get_name()
+                    .eq_ignore_ascii_case(file_name)
+            })
+            .collect::<Vec<_>>();
+        assets.sort_by(|a, b| a.git_ignore().get_flag().cmp(b.git_ignore().get_flag()));
+        flag.and_then(|flag| {
+            assets
                 .iter()
-                .chain(std::iter::once(&asset_name.as_term()))
-                .any(|term| asset.has_term(term))
-            {
-                Some(asset)
-            } else {
-                None
-            }
+                .find(|asset| asset.git_ignore().get_flag() == flag)
+                .copied()
         })
+        .or_else(|| assets.first().copied())
+    }
+    /// Determines which asset(s) the given name refers to. If no asset
+    /// has exactly the given name (ignoring ASCII case) and `fuzzy` is
+    /// true, then the best suggestion (see `AssetDB::suggest`) is used
+    /// instead, provided it scores at least `fuzzy::CONFIDENCE`.
+    pub fn resolve_name(&self, name: impl AsRef<str>, fuzzy: bool) -> Resolution<'_> {
+        let name = name.as_ref();
+        let mut exact = self
+            .assets
+            .iter()
+            .filter_map(|(asset_name, asset)| (*asset_name == name).then_some(asset))
+            .collect::<Vec<_>>();
+        exact.sort_by(|a, b| a.git_ignore().get_flag().cmp(b.git_ignore().get_flag()));
+        match exact.len() {
+            0 => (),
+            1 => return Resolution::Matched(exact[0]),
+            _ => return Resolution::Ambiguous(exact),
+        }
+        let suggestions = self.suggest(name, 3);
+        match suggestions.first() {
+            Some(best) if fuzzy && best.score >= fuzzy::CONFIDENCE => {
+                let tied = suggestions
+                    .iter()
+                    .filter(|s| s.score >= best.score)
+                    .map(|s| s.asset)
+                    .collect::<Vec<_>>();
+                if tied.len() > 1 {
+                    Resolution::Ambiguous(tied)
+                } else {
+                    Resolution::Approximate(best.clone())
+                }
+            }
+            _ => Resolution::NotFound(suggestions),
+        }
     }
     pub fn add_terms_for_asset_by_names<I: IntoIterator<Item = N>, N: AsRef<str>>(
         &mut self,
@@ -318,6 +666,84 @@ impl AssetDB {
     }
 }
 
+/// An asset suggested by `AssetDB::suggest`, along with its score and
+/// the term it was matched through (if not matched through its name).
+#[derive(Clone, Debug)]
+pub struct Suggestion<'a> {
+    pub asset_name: &'a AssetName,
+    pub asset: &'a Asset,
+    pub score: f64,
+    pub term: Option<Term>,
+}
+
+impl std::fmt::Display for Suggestion<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.asset_name.as_str())?;
+        if let Some(term) = self.term {
+            write!(f, " (via `{term}`)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of resolving a name to assets via `AssetDB::resolve_name`.
+#[derive(Clone, Debug)]
+pub enum Resolution<'a> {
+    /// Exactly one asset has the name.
+    Matched(&'a Asset),
+    /// No asset has the name, but the given suggestion is close enough.
+    Approximate(Suggestion<'a>),
+    /// Several assets (sorted by flag) have the name, or are equally
+    /// close to it.
+    Ambiguous(Vec<&'a Asset>),
+    /// No asset has the name; holds the closest suggestions, if any.
+    NotFound(Vec<Suggestion<'a>>),
+}
+
+impl<'a> Resolution<'a> {
+    /// Returns whether the name resolved to a single asset.
+    pub fn is_resolved(&self) -> bool {
+        matches!(self, Resolution::Matched(_) | Resolution::Approximate(_))
+    }
+    /// Returns the asset to use for the name, if any. For ambiguous
+    /// names, this is the first of the candidates.
+    pub fn asset(&self) -> Option<&'a Asset> {
+        match self {
+            Resolution::Matched(asset) => Some(asset),
+            Resolution::Approximate(suggestion) => Some(suggestion.asset),
+            Resolution::Ambiguous(assets) => assets.first().copied(),
+            Resolution::NotFound(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Resolution<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |asset: &Asset| {
+            let git_ignore = asset.git_ignore();
+            format!("{} ({})", git_ignore.get_name(), git_ignore.get_flag())
+        };
+        match self {
+            Resolution::Matched(asset) => write!(f, "{}", describe(asset)),
+            Resolution::Approximate(suggestion) => {
+                write!(f, "{} (approximate match)", describe(suggestion.asset))
+            }
+            Resolution::Ambiguous(assets) => {
+                let assets = assets.iter().map(|a| describe(a)).collect::<Vec<_>>();
+                write!(f, "ambiguous between {}", assets.join(", "))
+            }
+            Resolution::NotFound(suggestions) if suggestions.is_empty() => write!(f, "not found"),
+            Resolution::NotFound(suggestions) => {
+                let suggestions = suggestions
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "not found; did you mean {}?", suggestions.join(", "))
+            }
+        }
+    }
+}
+
 struct AssetDecorator<'a>(&'a mut AssetDB);
 
 impl<'a> AssetDecorator<'a> {
@@ -363,19 +789,275 @@ impl<'a> AssetDecorator<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_util::TempDir;
+
+    /// Returns the contents of the embedded `.gitignore` file with the
+    /// given file name and flag, if any.
+    fn embedded_contents(flag: &Flag, name: &str) -> Option<&'static str> {
+        EMBEDDED
+            .iter()
+            .find(|(f, n, _)| f == flag && *n == name)
+            .map(|(_, _, contents)| *contents)
+    }
+
+    /// Builds a database of the given templates, independently of the
+    /// templates available to the binary.
+    fn db(templates: &[(&str, Flag, &'static str)]) -> AssetDB {
+        let mut assets = HashMap::new();
+        for (name, flag, contents) in templates {
+            let asset_name = AssetName(name.to_string(), flag.clone());
+            let asset = Asset {
+                terms: vec![asset_name.as_term()],
+                git_ignore: GitIgnore {
+                    name: format!("{name}.gitignore"),
+                    flag: flag.clone(),
+                    contents: Contents::Embedded(contents),
+                },
+            };
+            assets.insert(asset_name, asset);
+        }
+        AssetDB { assets }
+    }
+
+    #[test]
+    fn test_embedded_assets_match_submodule() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let flags = [Flag::Default, Flag::Community, Flag::Global];
+        let flag_dirs = flags
+            .iter()
+            .map(|flag| root.join(flag.as_path()))
+            .collect::<Vec<_>>();
+        let mut on_disk = vec![];
+        for (flag, dir) in flags.iter().zip(flag_dirs.iter()) {
+            // nested templates belong to the flag of the directory they
+            // are found in, e.g., `community/JavaScript/Vue.gitignore`
+            let mut dirs = vec![dir.clone()];
+            while let Some(dir) = dirs.pop() {
+                let Ok(entries) = std::fs::read_dir(dir) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let name = entry.file_name().into_string().unwrap();
+                    if path.is_dir() && !name.starts_with('.') && !flag_dirs.contains(&path) {
+                        dirs.push(path);
+                    } else if path.is_file() && is_gitignore_file(&path) {
+                        on_disk.push((flag.clone(), name, std::fs::read_to_string(path).unwrap()));
+                    }
+                }
+            }
+        }
+        on_disk.sort();
+        let mut embedded = EMBEDDED
+            .iter()
+            .map(|(flag, name, contents)| (flag.clone(), name.to_string(), contents.to_string()))
+            .collect::<Vec<_>>();
+        embedded.sort();
+        assert_eq!(embedded, on_disk);
+        let assets = embedded_assets();
+        for (flag, name, contents) in on_disk {
+            let asset = assets.get_by_file_name(&name, Some(&flag)).unwrap();
+            assert_eq!(asset.git_ignore().get_flag(), &flag);
+            assert_eq!(asset.contents().unwrap(), contents);
+        }
+    }
+
+    #[test]
+    fn test_decorated_terms() {
+        let assets = db(&[
+            ("Node", Flag::Default, "node_modules/\n"),
+            ("Python", Flag::Default, "__pycache__/\n"),
+            ("Rust", Flag::Default, "/target/\n"),
+        ])
+        .decorated();
+        let names = |terms: &[&str]| {
+            let terms = Term::intern_iter(terms).collect::<Vec<_>>();
+            let mut names = assets
+                .filter_by_terms(&terms)
+                .map(|asset| asset.git_ignore().get_name().to_string())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        // file extensions and ecosystem names are terms of their
+        // templates, compared without regard to case
+        assert_eq!(names(&["py"]), vec!["Python.gitignore"]);
+        assert_eq!(
+            names(&["CARGO", "typescript"]),
+            vec!["Node.gitignore", "Rust.gitignore"]
+        );
+        assert!(names(&["haskell"]).is_empty());
+        // the undecorated database only knows the names
+        assert!(db(&[("Python", Flag::Default, "")])
+            .filter_by_terms(&[Term::intern("py")])
+            .next()
+            .is_none());
+    }
 
     #[test]
     fn test_rust_gitignore_undecorated() {
-        let assets = AssetDB::default();
+        let assets = AssetDB::from_sources(&[AssetSource::Embedded]);
         let asset_name = AssetName(String::from("Rust"), Flag::Default);
         let asset = Asset {
             terms: vec![Term::intern("Rust")],
             git_ignore: GitIgnore {
                 name: String::from("Rust.gitignore"),
                 flag: Flag::Default,
+                contents: Contents::Embedded(
+                    embedded_contents(&Flag::Default, "Rust.gitignore").unwrap(),
+                ),
             },
         };
         assert_eq!(assets.get_by_name("Rust"), Some(&asset));
         assert_eq!(assets.get(&asset_name), assets.get_by_name("Rust"));
     }
+
+    fn resolved_names(resolution: &Resolution) -> Vec<String> {
+        let assets = match resolution {
+            Resolution::Matched(asset) => vec![*asset],
+            Resolution::Approximate(suggestion) => vec![suggestion.asset],
+            Resolution::Ambiguous(assets) => assets.clone(),
+            Resolution::NotFound(suggestions) => suggestions.iter().map(|s| s.asset).collect(),
+        };
+        assets
+            .iter()
+            .map(|asset| asset.git_ignore().get_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_name_fuzzy() {
+        let assets = db(&[
+            ("Python", Flag::Default, ""),
+            ("Rust", Flag::Default, ""),
+            ("Node", Flag::Default, ""),
+        ]);
+        // a single close candidate is only picked when asked to
+        let resolution = assets.resolve_name("pyhton", true);
+        assert!(
+            matches!(resolution, Resolution::Approximate(_)),
+            "{resolution:?}"
+        );
+        assert_eq!(resolved_names(&resolution), vec!["Python.gitignore"]);
+        let resolution = assets.resolve_name("pyhton", false);
+        assert!(
+            matches!(resolution, Resolution::NotFound(_)),
+            "{resolution:?}"
+        );
+        assert_eq!(resolved_names(&resolution), vec!["Python.gitignore"]);
+        assert!(!resolution.is_resolved());
+        // a candidate that isn't close enough is only suggested
+        let resolution = assets.resolve_name("rst", true);
+        assert!(
+            matches!(resolution, Resolution::NotFound(_)),
+            "{resolution:?}"
+        );
+        assert_eq!(resolved_names(&resolution), vec!["Rust.gitignore"]);
+    }
+
+    #[test]
+    fn test_resolve_name_ties() {
+        // equally close candidates are never picked automatically
+        let assets = db(&[("Pythan", Flag::Default, ""), ("Pythen", Flag::Default, "")]);
+        let resolution = assets.resolve_name("python", true);
+        assert!(
+            matches!(resolution, Resolution::Ambiguous(_)),
+            "{resolution:?}"
+        );
+        assert_eq!(
+            resolved_names(&resolution),
+            vec!["Pythan.gitignore", "Pythen.gitignore"]
+        );
+        assert!(!resolution.is_resolved());
+
+        // neither are several templates with the same name, which are
+        // sorted by flag
+        let assets = db(&[("Vim", Flag::Global, ""), ("vim", Flag::Default, "")]);
+        let resolution = assets.resolve_name("VIM", false);
+        match &resolution {
+            Resolution::Ambiguous(candidates) => {
+                assert_eq!(candidates[0].git_ignore().get_flag(), &Flag::Default);
+                assert_eq!(candidates[1].git_ignore().get_flag(), &Flag::Global);
+            }
+            resolution => panic!("expected an ambiguous name, found {resolution:?}"),
+        }
+        assert_eq!(
+            resolution.asset().unwrap().git_ignore().get_flag(),
+            &Flag::Default
+        );
+    }
+
+    #[test]
+    fn test_overlay_keeps_names_shared_within_a_source() {
+        let mut assets = db(&[("Foo", Flag::Default, "old\n")]);
+        assets.overlay(db(&[
+            ("Foo", Flag::Default, "default\n"),
+            ("Foo", Flag::Global, "global\n"),
+            ("foo", Flag::Community, "community\n"),
+        ]));
+        let mut contents = assets
+            .filter_by_name("FOO")
+            .map(|asset| asset.contents().unwrap())
+            .collect::<Vec<_>>();
+        contents.sort();
+        assert_eq!(contents, vec!["community\n", "default\n", "global\n"]);
+        match assets.resolve_name("foo", false) {
+            Resolution::Ambiguous(candidates) => assert_eq!(candidates.len(), 3),
+            resolution => panic!("expected an ambiguous name, found {resolution:?}"),
+        }
+    }
+
+    #[test]
+    fn test_later_sources_override_earlier() {
+        let dir = TempDir::new();
+        dir.write("first/Bazel.gitignore", "bazel-*\n");
+        dir.write("first/Tool.gitignore", "*.tool\n");
+        dir.write("second/bazel.gitignore", "/bazel-out/\n");
+        let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+        let assets = AssetDB::from_sources(&[
+            AssetSource::Embedded,
+            AssetSource::Dir(first),
+            AssetSource::Dir(second.clone()),
+            AssetSource::Dir(dir.path().join("missing")),
+        ]);
+        let bazel = assets.get_by_name("Bazel").unwrap();
+        assert_eq!(
+            bazel.git_ignore().get_filepath(),
+            second.join("bazel.gitignore")
+        );
+        assert_eq!(bazel.contents().unwrap(), "/bazel-out/\n");
+        assert_eq!(bazel.git_ignore().get_flag(), &Flag::Custom(second.clone()));
+        assert_eq!(assets.filter_by_name("Bazel").count(), 1);
+        assert!(assets.get_by_name("Tool").is_some());
+    }
+
+    #[test]
+    fn test_local_flags_are_stable() {
+        let dir = TempDir::new();
+        dir.write(".ignore_me/templates/Rust.gitignore", "/build/\n");
+        let assets =
+            AssetDB::from_sources(&[AssetSource::Embedded, AssetSource::local(dir.path())]);
+        let local = Flag::Custom(PathBuf::from(AssetSource::LOCAL_SUBDIR));
+        // the project-local directory is shown relative to the root,
+        // wherever the project is and whatever the working directory
+        assert_eq!(local.to_string(), "custom: .ignore_me/templates");
+        for flag in [Flag::Default, Flag::Global, local.clone()] {
+            assert_eq!(Flag::parse(&flag.to_string()), Some(flag));
+        }
+        assert_eq!(Flag::parse("custom"), None);
+        let asset = assets
+            .get_by_file_name(
+                "rust.gitignore",
+                Flag::parse("custom: .ignore_me/templates").as_ref(),
+            )
+            .unwrap();
+        assert_eq!(asset.git_ignore().get_flag(), &local);
+        assert_eq!(asset.contents().unwrap(), "/build/\n");
+        // a template no longer coming from the recorded source is still
+        // found by its name
+        let asset = assets
+            .get_by_file_name("C.gitignore", Some(&local))
+            .unwrap();
+        assert_eq!(asset.git_ignore().get_flag(), &Flag::Default);
+    }
 }
@@ -0,0 +1,104 @@
+//! Removal of rules repeated across the sections of a generated file,
+//! e.g., `*.o` in both `C.gitignore` and `C++.gitignore`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::pattern;
+
+/// The prefix of the comment preceding a rule that other templates
+/// contain as well, e.g., `# also in: C++.gitignore`.
+pub const ALSO_IN: &str = "# also in: ";
+
+/// Removes from the given section bodies, each belonging to the
+/// template with the given name, every rule whose normalized form (see
+/// `pattern::Rule`) appeared on an earlier line, keeping the first
+/// occurrence. Since a rule may override any earlier rule of the
+/// opposite polarity, a repeated rule is kept if such a rule appeared
+/// since its previous occurrence. The kept occurrence of a rule other
+/// templates contain as well is preceded by a comment naming them.
+pub fn dedupe(sections: &[(&str, &str)]) -> Vec<String> {
+    // the position of the occurrence of each rule that later ones are
+    // removed in favour of, along with the number of rules of the
+    // opposite polarity emitted before it
+    let mut kept = HashMap::new();
+    let mut also_in = HashMap::<(usize, usize), Vec<&str>>::new();
+    let mut removed = HashSet::new();
+    // the number of rules emitted that ignore and re-include paths
+    let mut emitted = [0, 0];
+    for (i, &(name, body)) in sections.iter().enumerate() {
+        for (j, text) in body.lines().enumerate() {
+            let rule = match pattern::parse_pattern(text) {
+                Ok(Some(rule)) => rule,
+                _ => continue,
+            };
+            let polarity = usize::from(rule.negated);
+            let opposite = emitted[1 - polarity];
+            match kept.get(&rule.to_string()) {
+                Some(&(at, seen)) if seen == opposite => {
+                    let (first, _) = at;
+                    let names = also_in.entry(at).or_default();
+                    if sections[first].0 != name && !names.contains(&name) {
+                        names.push(name);
+                    }
+                    removed.insert((i, j));
+                }
+                _ => {
+                    kept.insert(rule.to_string(), ((i, j), opposite));
+                    emitted[polarity] += 1;
+                }
+            }
+        }
+    }
+    sections
+        .iter()
+        .enumerate()
+        .map(|(i, &(_, body))| {
+            let mut buf = String::new();
+            for (j, text) in body.lines().enumerate() {
+                if removed.contains(&(i, j)) {
+                    continue;
+                }
+                if let Some(names) = also_in.get(&(i, j)).filter(|names| !names.is_empty()) {
+                    buf.push_str(ALSO_IN);
+                    buf.push_str(&names.join(", "));
+                    buf.push('\n');
+                }
+                buf.push_str(text);
+                buf.push('\n');
+            }
+            buf
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dedupe() {
+        let bodies = dedupe(&[
+            ("C.gitignore", "# Objects\n*.o\n/build/\n"),
+            ("C++.gitignore", "*.o\n*.a\nbuild\n"),
+            ("CMake.gitignore", "**/*.o\n/build/\n"),
+        ]);
+        assert_eq!(
+            bodies,
+            vec![
+                "# Objects\n# also in: C++.gitignore, CMake.gitignore\n*.o\n\
+                 # also in: CMake.gitignore\n/build/\n",
+                "*.a\nbuild\n",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_keeps_rules_after_negations() {
+        let bodies = dedupe(&[
+            ("A.gitignore", "*.log\n!keep.log\n"),
+            ("B.gitignore", "*.log\n!keep.log\n"),
+        ]);
+        assert_eq!(bodies[1], "*.log\n!keep.log\n");
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::assets::{Asset, AssetDB, AssetName};
+
+/// Directories that are never descended into while scanning, as they
+/// either belong to version control or hold build outputs and
+/// third-party code that says nothing about the project itself.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist-newstyle", "__pycache__"];
+
+/// How deep below the root the scan will descend.
+const MAX_DEPTH: usize = 8;
+
+/// Only candidates with more than this fraction of the hits of the
+/// best candidate are considered top matches.
+const TOP_RATIO: f64 = 0.25;
+
+/// Upper bound on the number of top matches.
+const MAX_MATCHES: usize = 5;
+
+/// An asset along with the number of files in the scanned directory
+/// tree whose extension or file name corresponds to one of its terms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate<'a> {
+    pub asset: &'a Asset,
+    pub hits: usize,
+}
+
+impl std::fmt::Display for Candidate<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} hits)",
+            self.asset.git_ignore().get_name(),
+            self.hits
+        )
+    }
+}
+
+/// Scans the directory tree rooted at `root` and ranks the assets in
+/// `asset_db` by how many files hit them. A file hits an asset if
+/// either its file name (e.g., `Cargo.toml`) or its extension (e.g.,
+/// `rs`) is one of the asset's terms, so the given database should be
+/// decorated (see `AssetDB::new_decorated`) for anything meaningful to
+/// be found.
+///
+/// Candidates are returned in descending order of hits, with ties
+/// broken by name.
+pub fn detect(asset_db: &AssetDB, root: impl AsRef<Path>) -> io::Result<Vec<Candidate<'_>>> {
+    let index = term_index(asset_db);
+    let mut hits: HashMap<&AssetName, usize> = HashMap::new();
+    let mut dirs = vec![(root.as_ref().to_path_buf(), 0)];
+    while let Some((dir, depth)) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // an unreadable subdirectory only means fewer hits
+            Err(e) if depth > 0 && e.kind() == io::ErrorKind::PermissionDenied => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if path.is_dir() {
+                if depth < MAX_DEPTH && !is_skipped_dir(&file_name) {
+                    dirs.push((path, depth + 1));
+                }
+                continue;
+            }
+            for key in file_keys(&path, &file_name) {
+                for asset_name in index.get(&key).into_iter().flatten() {
+                    *hits.entry(asset_name).or_default() += 1;
+                }
+            }
+        }
+    }
+    let mut candidates = hits
+        .into_iter()
+        .filter_map(|(asset_name, hits)| {
+            asset_db
+                .get(asset_name)
+                .map(|asset| Candidate { asset, hits })
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| {
+        b.hits.cmp(&a.hits).then_with(|| {
+            a.asset
+                .git_ignore()
+                .get_name()
+                .cmp(b.asset.git_ignore().get_name())
+        })
+    });
+    Ok(candidates)
+}
+
+/// Returns the leading candidates worth generating a `.gitignore`
+/// from, i.e., those with more than `TOP_RATIO` of the best
+/// candidate's hits, up to `MAX_MATCHES` of them. Expects the
+/// candidates in the order returned by `detect`.
+pub fn top_matches<'a>(candidates: &[Candidate<'a>]) -> Vec<Candidate<'a>> {
+    let best = match candidates.first() {
+        Some(c) => c.hits as f64,
+        None => return vec![],
+    };
+    candidates
+        .iter()
+        .take_while(|c| c.hits as f64 > best * TOP_RATIO)
+        .take(MAX_MATCHES)
+        .cloned()
+        .collect()
+}
+
+fn is_skipped_dir(name: &str) -> bool {
+    name.starts_with('.') || SKIPPED_DIRS.contains(&name)
+}
+
+/// Maps every (lowercased) term to the names of the assets carrying it.
+fn term_index(asset_db: &AssetDB) -> HashMap<String, Vec<&AssetName>> {
+    let mut index: HashMap<String, Vec<&AssetName>> = HashMap::new();
+    for (asset_name, asset) in asset_db.assets() {
+        for term in asset.terms_iter() {
+            let names = index.entry(term.as_str().to_ascii_lowercase()).or_default();
+            if !names.contains(&asset_name) {
+                names.push(asset_name);
+            }
+        }
+    }
+    index
+}
+
+/// The (lowercased) keys under which a file is looked up: its file
+/// name and, if it has one, its extension.
+fn file_keys(path: &Path, file_name: &str) -> Vec<String> {
+    let mut keys = vec![file_name.to_ascii_lowercase()];
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        let ext = ext.to_ascii_lowercase();
+        if ext != keys[0] {
+            keys.push(ext);
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assets::AssetSource, test_util::TempDir};
+
+    fn names(candidates: &[Candidate<'_>]) -> Vec<(String, usize)> {
+        candidates
+            .iter()
+            .map(|c| (c.asset.git_ignore().get_name().to_string(), c.hits))
+            .collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        let templates = TempDir::new();
+        for name in ["Rust", "Python", "Go"] {
+            templates.write(format!("{name}.gitignore"), "");
+        }
+        let asset_db =
+            AssetDB::from_sources(&[AssetSource::Dir(templates.path().to_path_buf())]).decorated();
+        let project = TempDir::new();
+        for path in [
+            "Cargo.toml",
+            "src/main.rs",
+            "src/lib.rs",
+            "scripts/gen.py",
+            "target/debug/build.rs",
+            ".hidden/x.go",
+        ] {
+            project.write(path, "");
+        }
+        let candidates = detect(&asset_db, project.path()).unwrap();
+        assert_eq!(
+            names(&candidates),
+            vec![
+                (String::from("Rust.gitignore"), 3),
+                (String::from("Python.gitignore"), 1)
+            ]
+        );
+        assert_eq!(top_matches(&candidates), candidates);
+        assert!(top_matches(&candidates[..0]).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detect_skips_unreadable_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let templates = TempDir::new();
+        templates.write("Rust.gitignore", "");
+        let asset_db =
+            AssetDB::from_sources(&[AssetSource::Dir(templates.path().to_path_buf())]).decorated();
+        let project = TempDir::new();
+        project.write("src/main.rs", "");
+        let locked = project.write("locked/lib.rs", "");
+        let locked = locked.parent().unwrap();
+        fs::set_permissions(locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // root can still read the locked directory, leaving nothing to test
+        if fs::read_dir(locked).is_ok() {
+            fs::set_permissions(locked, fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+        let result = detect(&asset_db, project.path()).map(|candidates| names(&candidates));
+        fs::set_permissions(locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(result.unwrap(), vec![(String::from("Rust.gitignore"), 1)]);
+    }
+}
@@ -0,0 +1,196 @@
+//! Line-based diffs between the existing and generated contents of a
+//! file, rendered in the unified format.
+
+/// The number of unchanged lines shown around each change.
+pub const CONTEXT: usize = 3;
+
+/// A single line of a diff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+impl Edit<'_> {
+    fn is_equal(&self) -> bool {
+        matches!(self, Edit::Equal(_))
+    }
+}
+
+/// Returns the shortest sequence of edits turning the lines of `old`
+/// into the lines of `new`, computed with Myers' algorithm.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Edit<'a>> {
+    let a = old.lines().collect::<Vec<_>>();
+    let b = new.lines().collect::<Vec<_>>();
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    if max == 0 {
+        return vec![];
+    }
+    // `v[k + max]` holds the furthest `x` reached on diagonal `k`, and
+    // `trace[d]` the state of `v` before exploring edit distance `d`
+    let mut v = vec![0isize; 2 * max as usize + 1];
+    let mut trace = vec![];
+    let idx = |k: isize| (k + max) as usize;
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[idx(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal(a[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(b[y as usize - 1]));
+            } else {
+                edits.push(Edit::Delete(a[x as usize - 1]));
+            }
+            x = prev_x;
+            y = prev_y;
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/// Renders the differences between `old` and `new` as a unified diff
+/// with `CONTEXT` lines of context, labelling the two sides with the
+/// given labels. Returns `None` if there are no differences.
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> Option<String> {
+    let edits = diff_lines(old, new);
+    if edits.iter().all(Edit::is_equal) {
+        return None;
+    }
+    let mut out = format!("--- {old_label}\n+++ {new_label}\n");
+    // the line numbers (0-based) in `old` and `new` at each edit
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in edits.iter() {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Equal(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Delete(_) => old_line += 1,
+            Edit::Insert(_) => new_line += 1,
+        }
+    }
+    let mut i = 0;
+    while i < edits.len() {
+        let first_change = match (i..edits.len()).find(|&j| !edits[j].is_equal()) {
+            Some(j) => j,
+            None => break,
+        };
+        let start = first_change.saturating_sub(CONTEXT).max(i);
+        // extend the hunk while the next change is close enough for
+        // the context around both to overlap
+        let mut last_change = first_change;
+        let mut j = first_change + 1;
+        while j < edits.len() && j <= last_change + 2 * CONTEXT + 1 {
+            if !edits[j].is_equal() {
+                last_change = j;
+            }
+            j += 1;
+        }
+        let end = (last_change + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+        let (old_start, new_start) = positions[start];
+        let old_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        ));
+        for edit in hunk {
+            let (prefix, line) = match edit {
+                Edit::Equal(line) => (' ', line),
+                Edit::Delete(line) => ('-', line),
+                Edit::Insert(line) => ('+', line),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+        i = end;
+    }
+    Some(out)
+}
+
+/// Formats the range of a hunk as `start,len`, where `start` is 1-based
+/// unless the range is empty (in which case it is the line before).
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{start},0")
+    } else {
+        format!("{},{len}", start + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let edits = diff_lines("a\nb\nc\n", "a\nc\nd\n");
+        assert_eq!(
+            edits,
+            vec![
+                Edit::Equal("a"),
+                Edit::Delete("b"),
+                Edit::Equal("c"),
+                Edit::Insert("d"),
+            ]
+        );
+        assert_eq!(diff_lines("", "x\n"), vec![Edit::Insert("x")]);
+    }
+
+    #[test]
+    fn test_unified() {
+        assert_eq!(unified("a\n", "a\n", "old", "new"), None);
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        let expected = "--- old\n+++ new\n\
+            @@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n\
+            @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n";
+        assert_eq!(unified(old, new, "old", "new").unwrap(), expected);
+    }
+}
@@ -1,30 +1,41 @@
 use std::{
-    fs, io,
+    fs,
+    io::{self, Write},
     path::{Path, PathBuf},
 };
 
-use crate::assets::Asset;
+use crate::{
+    assets::{Asset, GitIgnore},
+    dedupe,
+    managed::{self, Block},
+};
+
+/// The outcome of generating a file, as determined by `FileGen::plan`.
+#[derive(Clone, Debug)]
+pub struct Plan {
+    /// The path of the output file.
+    pub path: PathBuf,
+    /// The contents of the output file prior to writing, if it exists.
+    pub existing: Option<String>,
+    /// The contents to be written to the output file.
+    pub contents: String,
+    /// Where the existing output file will be backed up to, if at all.
+    pub backup: Option<PathBuf>,
+}
 
+#[derive(Default)]
 pub struct FileGen {
     assets: Vec<Asset>,
     target: Option<PathBuf>,
-}
-
-impl Default for FileGen {
-    fn default() -> Self {
-        Self {
-            assets: vec![],
-            target: None,
-        }
-    }
+    filename: Option<PathBuf>,
+    prune: bool,
+    dedupe: bool,
+    preserve: bool,
+    create_target: bool,
 }
 
 impl FileGen {
     pub const FILENAME: &'static str = ".gitignore";
-    pub fn new() -> Self {
-        Self::default()
-    }
-
     pub fn with_assets(assets: impl IntoIterator<Item = Asset>) -> Self {
         let assets = assets.into_iter().fold(vec![], |mut a, c| {
             if !a.contains(&c) {
@@ -34,81 +45,124 @@ impl FileGen {
         });
         Self {
             assets,
-            target: None,
-        }
-    }
-
-    pub fn with_target(target: impl AsRef<Path>) -> Self {
-        let mut this = Self {
-            assets: vec![],
-            target: None,
-        };
-        let path = target.as_ref();
-        if path.is_dir() && path.exists() {
-            this.target = Some(path.to_path_buf());
+            ..Self::default()
         }
-        this
     }
 
     /// Creates an instance of `FileGen` containing the given assets
     /// with the provided `target` as the directory of the output
-    /// file. If the provided `target` is not a directory (and/or does
-    /// not exist), then it will be ignored (and the file will be
-    /// written instead to the current working directory).
+    /// file, returning an error if the provided `target` is not an
+    /// existing directory.
     pub fn with_assets_and_target(
         assets: impl IntoIterator<Item = Asset>,
         target: impl AsRef<Path>,
-    ) -> Self {
+    ) -> io::Result<Self> {
         let mut this = Self::with_assets(assets);
         let path = target.as_ref();
-        if path.is_dir() && path.exists() {
-            this.target = Some(target.as_ref().to_path_buf());
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the directory `{}` does not exist", path.display()),
+            ));
         }
-        this
+        this.target = Some(path.to_path_buf());
+        Ok(this)
     }
 
-    /// Adds an `Asset` to include when generating the `.gitignore`
-    /// file. If the `Asset` already exists, then this does nothing.
-    pub fn add_asset(&mut self, asset: Asset) {
-        if !self.assets.contains(&asset) {
-            self.assets.push(asset);
+    /// Sets `target` as the directory of the output file like
+    /// `FileGen::with_assets_and_target`, except that the directory
+    /// need not exist, as it is created when writing (see
+    /// `FileGen::write_plan`).
+    pub fn with_created_target(mut self, target: impl AsRef<Path>) -> Self {
+        self.target = Some(target.as_ref().to_path_buf());
+        self.create_target = true;
+        self
+    }
+
+    /// Sets the name of the output file (by default `.gitignore`),
+    /// which is resolved relative to the `target` directory, returning
+    /// an error if the directory the output file would be written to
+    /// does not exist.
+    pub fn with_filename(mut self, filename: impl AsRef<Path>) -> io::Result<Self> {
+        self.filename = Some(filename.as_ref().to_path_buf());
+        let path = self.get_target_path()?;
+        match path.parent() {
+            Some(dir) if !(dir.is_dir() || self.create_target) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the directory `{}` does not exist", dir.display()),
+            )),
+            _ => Ok(self),
         }
     }
 
+    /// Removes the managed sections of templates not included when
+    /// regenerating an existing file (see `FileGen::render_merged`),
+    /// instead of keeping them.
+    pub fn pruning_sections(mut self) -> Self {
+        self.prune = true;
+        self
+    }
+
+    /// Keeps the contents of an existing output file without managed
+    /// sections, appending the sections to it (see
+    /// `FileGen::render_append`) instead of backing it up and
+    /// overwriting it.
+    pub fn preserving_existing(mut self) -> Self {
+        self.preserve = true;
+        self
+    }
+
+    /// Removes rules repeated across (or within) the sections of the
+    /// generated file (see `dedupe::dedupe`).
+    pub fn deduplicating(mut self) -> Self {
+        self.dedupe = true;
+        self
+    }
+
     /// Returns the path where the output will be written by joining
-    /// the path contained in the `target` field with the file name
-    /// `.gitignore`.
-    ///
+    /// the path contained in the `target` field with the output file
+    /// name (`.gitignore` unless set via `FileGen::with_filename`).
     ///
-    /// If the `target` field is not a directory, or is empty, then
-    /// the current working directory will be used and will return an error if a
-    /// valid current working directory cannot be obtained.
+    /// If the `target` field is empty, then the current working
+    /// directory will be used and will return an error if a valid
+    /// current working directory cannot be obtained.
     pub fn get_target_path(&self) -> io::Result<PathBuf> {
+        let filename = match self.filename.as_ref() {
+            Some(filename) => filename.as_path(),
+            None => Self::FILENAME.as_ref(),
+        };
         match self.target.as_ref() {
-            Some(p) if p.is_dir() && p.exists() => Ok(p.join(Self::FILENAME)),
-            _ => std::env::current_dir().map(|cwd| cwd.join(Self::FILENAME)),
-        }
-    }
-
-    fn backup_if_exists(&self, path: &PathBuf) -> io::Result<()> {
-        if path.exists() {
-            // `<DIR>/.gitignore` exists, so we find out for what integer
-            // `n` will `DIR/.gitignore.backup_n` not exist and rename
-            // the existing file to that.
-            let name = ".gitignore.backup_";
-            let mut ct = 1;
-            let mut target = path.clone();
-            loop {
-                let end = format!("{name}{}", ct);
-                let p = target.with_file_name(end);
-                if p.exists() {
-                    ct += 1;
-                    continue;
-                } else {
-                    target = p;
-                    break;
-                }
+            Some(p) => Ok(p.join(filename)),
+            None => std::env::current_dir().map(|cwd| cwd.join(filename)),
+        }
+    }
+
+    /// Returns the path the file at `path` would be backed up to
+    /// before being overwritten, i.e., `<DIR>/<FILE>.backup_n` (e.g.,
+    /// `.gitignore.backup_1`) for the first integer `n` for which that
+    /// file does not exist yet, or `None` if there is no file at `path`
+    /// to back up.
+    pub fn backup_path(path: &Path) -> Option<PathBuf> {
+        if !path.exists() {
+            return None;
+        }
+        let name = match path.file_name() {
+            Some(name) => format!("{}.backup_", name.to_string_lossy()),
+            None => format!("{}.backup_", Self::FILENAME),
+        };
+        let mut ct = 1;
+        loop {
+            let p = path.with_file_name(format!("{name}{ct}"));
+            if p.exists() {
+                ct += 1;
+            } else {
+                return Some(p);
             }
+        }
+    }
+
+    fn backup_if_exists(&self, path: &Path) -> io::Result<()> {
+        if let Some(target) = Self::backup_path(path) {
             println!(
                 "Backing up\n    `{}`\nto\n    `{}`",
                 path.display(),
@@ -119,11 +173,206 @@ impl FileGen {
         Ok(())
     }
 
+    /// Returns the header line preceding the contents of the given
+    /// `.gitignore` file in generated output, naming the file along
+    /// with where it came from, e.g., `# [Rust.gitignore] (default)`.
+    pub fn section_header(git_ignore: &GitIgnore) -> String {
+        format!("# [{}] ({})", git_ignore.get_name(), git_ignore.get_flag())
+    }
+
+    /// Returns the names of the `.gitignore` files whose sections are
+    /// present in the given contents, as identified by the headers
+    /// emitted by `FileGen::section_header`.
+    pub fn section_names(contents: &str) -> Vec<&str> {
+        contents
+            .lines()
+            .filter_map(|line| managed::parse_header(line).map(|(name, _)| name))
+            .collect()
+    }
+
+    /// Returns the section for the given `Asset` exactly as it appears
+    /// in generated output, i.e., its contents preceded by the header
+    /// returned by `FileGen::section_header` and wrapped in the markers
+    /// delimiting a managed section (see the `managed` module).
+    pub fn render_section(asset: &Asset) -> io::Result<String> {
+        let mut buf = String::new();
+        Self::push_section(&mut buf, asset, &Self::section_body(asset)?);
+        Ok(buf)
+    }
+
+    /// Returns the body of the section for the given `Asset`, i.e., its
+    /// contents terminated by a newline.
+    pub fn section_body(asset: &Asset) -> io::Result<String> {
+        let mut body = asset.contents()?;
+        if !(body.is_empty() || body.ends_with('\n')) {
+            body.push('\n');
+        }
+        Ok(body)
+    }
+
+    /// Returns each `Asset` along with the body of its section as it
+    /// appears in generated output, which differs from
+    /// `FileGen::section_body` if `FileGen::deduplicating` was called.
+    pub fn section_bodies(&self) -> io::Result<Vec<(&Asset, String)>> {
+        let bodies = self
+            .assets
+            .iter()
+            .map(Self::section_body)
+            .collect::<io::Result<Vec<_>>>()?;
+        let bodies = if self.dedupe {
+            let sections = self
+                .assets
+                .iter()
+                .zip(bodies.iter())
+                .map(|(asset, body)| (asset.git_ignore().get_name(), body.as_str()))
+                .collect::<Vec<_>>();
+            dedupe::dedupe(&sections)
+        } else {
+            bodies
+        };
+        Ok(self.assets.iter().zip(bodies).collect())
+    }
+
+    /// Pushes the managed section for the given `Asset` with the given
+    /// body, from its begin marker up to and including the line of its
+    /// end marker.
+    fn push_managed(buf: &mut String, asset: &Asset, body: &str) {
+        let git_ignore = asset.git_ignore();
+        let name = git_ignore.get_name();
+        let flag = git_ignore.get_flag().to_string();
+        buf.push_str(&managed::begin_marker(name, &flag, body));
+        buf.push('\n');
+        buf.push_str(&Self::section_header(git_ignore));
+        buf.push('\n');
+        buf.push_str(body);
+        buf.push_str(&managed::end_marker(name));
+        buf.push('\n');
+    }
+
+    fn push_section(buf: &mut String, asset: &Asset, body: &str) {
+        Self::push_managed(buf, asset, body);
+        buf.push('\n');
+    }
+
+    /// Returns the aggregated content of all `Asset`s exactly as
+    /// `FileGen::write_to_target` would write it to a new file.
+    pub fn render(&self) -> io::Result<String> {
+        let mut buf = String::new();
+        buf.push_str("# GENERATED BY `ignore_me` COMMAND LINE UTILITY\n\n");
+        for (asset, body) in self.section_bodies()? {
+            Self::push_section(&mut buf, asset, &body);
+        }
+        Ok(buf)
+    }
+
+    /// Returns the given `existing` contents with its managed sections
+    /// regenerated from the `Asset`s, or `None` if `existing` contains
+    /// no managed sections. Sections of included `Asset`s are replaced
+    /// in place, sections of other templates are kept (unless
+    /// `FileGen::pruning_sections` was called), and sections of
+    /// `Asset`s not present yet are appended, while all text outside of
+    /// managed sections is kept as is.
+    ///
+    /// The contents are returned along with whether a section edited by
+    /// hand (see `Section::is_modified`) was replaced or removed.
+    pub fn render_merged(&self, existing: &str) -> io::Result<Option<(String, bool)>> {
+        let blocks = managed::parse(existing);
+        if !blocks.iter().any(|b| matches!(b, Block::Section(_))) {
+            return Ok(None);
+        }
+        let bodies = self.section_bodies()?;
+        let mut buf = String::new();
+        let mut emitted = vec![];
+        let mut removed = false;
+        let mut edits_lost = false;
+        for block in blocks {
+            match block {
+                Block::Text(text) => {
+                    // drop the blank line separating a removed section
+                    // from what follows
+                    let text = match text.strip_prefix('\n') {
+                        Some(rest) if removed => rest,
+                        _ => text,
+                    };
+                    buf.push_str(text);
+                    removed = false;
+                }
+                Block::Section(section) => {
+                    let i = (0..self.assets.len()).find(|i| {
+                        self.assets[*i].git_ignore().get_name() == section.name
+                            && !emitted.contains(i)
+                    });
+                    match i {
+                        Some(i) => {
+                            Self::push_managed(&mut buf, bodies[i].0, &bodies[i].1);
+                            emitted.push(i);
+                            edits_lost |= section.is_modified();
+                        }
+                        None if !self.prune => buf.push_str(section.text),
+                        None => {
+                            removed = true;
+                            edits_lost |= section.is_modified();
+                        }
+                    }
+                }
+            }
+        }
+        let rest = (0..self.assets.len())
+            .filter(|i| !emitted.contains(i))
+            .collect::<Vec<_>>();
+        if !rest.is_empty() {
+            if !buf.is_empty() && !buf.ends_with("\n\n") {
+                buf.push_str(if buf.ends_with('\n') { "\n" } else { "\n\n" });
+            }
+            for i in rest {
+                Self::push_section(&mut buf, bodies[i].0, &bodies[i].1);
+            }
+        }
+        Ok(Some((buf, edits_lost)))
+    }
+
+    /// Determines what `FileGen::write_to_target` would do without
+    /// touching the filesystem.
+    pub fn plan(&self) -> io::Result<Plan> {
+        let path = self.get_target_path()?;
+        let existing = match fs::read_to_string(&path) {
+            Ok(existing) => Some(existing),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        self.plan_from(existing)
+    }
+
+    /// Like `FileGen::plan`, but with the given contents standing in
+    /// for those of the output file.
+    pub fn plan_from(&self, existing: Option<String>) -> io::Result<Plan> {
+        let path = self.get_target_path()?;
+        let merged = match existing.as_ref() {
+            Some(existing) => self.render_merged(existing)?,
+            None => None,
+        };
+        let (contents, backup) = match (merged, existing.as_ref()) {
+            // keep a copy of sections edited by hand
+            (Some((contents, true)), _) => (contents, Self::backup_path(&path)),
+            (Some((contents, false)), _) => (contents, None),
+            (None, Some(existing)) if self.preserve => {
+                (existing.clone() + &self.render_append(existing)?, None)
+            }
+            (None, _) => (self.render()?, Self::backup_path(&path)),
+        };
+        Ok(Plan {
+            path,
+            existing,
+            contents,
+            backup,
+        })
+    }
+
     /// Reads the content of all `Asset`s and, if there is at least
     /// one asset *and* no failures occurred, writes the aggregated
-    /// content to a file named `.gitignore` in the directory
-    /// described by the `target` field, returning the number of bytes
-    /// written.
+    /// content to the output file (`.gitignore` by default) in the
+    /// directory described by the `target` field, returning the number
+    /// of bytes written.
     ///
     /// If there are no `Asset`s included, then this does nothing and
     /// returns a result indicating zero bytes were written.
@@ -131,32 +380,203 @@ impl FileGen {
     /// If the `target` field is `None`, then uses the current working
     /// directory.
     ///
-    /// If the final output path already exists, then it will make a
-    /// backup of the existing file and overwrite it.
+    /// If the final output path already exists and contains managed
+    /// sections, then only those are regenerated (see
+    /// `FileGen::render_merged`), backing up the file first if any of
+    /// them was edited by hand. Otherwise, it will make a backup of
+    /// the existing file and overwrite it, unless
+    /// `FileGen::preserving_existing` was called.
     pub fn write_to_target(&self) -> io::Result<usize> {
+        if self.assets.is_empty() {
+            return Ok(0);
+        }
+        self.write_plan(&self.plan()?)
+    }
+
+    /// Carries out the given plan, backing up the existing output file
+    /// if the plan says so, and returns the number of bytes written.
+    pub fn write_plan(&self, plan: &Plan) -> io::Result<usize> {
+        if let (true, Some(dir)) = (self.create_target, plan.path.parent()) {
+            fs::create_dir_all(dir)?;
+        }
+        if plan.backup.is_some() {
+            self.backup_if_exists(&plan.path)?;
+        }
+        fs::write(&plan.path, &plan.contents)?;
+        Ok(plan.contents.len())
+    }
+
+    /// Returns the text `FileGen::append_to_target` would append to a
+    /// file with the given `existing` contents, i.e., the sections of
+    /// every `Asset` not already present in `existing`. This is empty
+    /// if there is nothing to append.
+    pub fn render_append(&self, existing: &str) -> io::Result<String> {
+        let present = Self::section_names(existing);
         let mut buf = String::new();
-        buf.push_str("# GENERATED BY `ignore_me` COMMAND LINE UTILITY\n\n");
-        let mut nonempty = false;
-        for asset in self.assets.as_slice() {
-            let contents = asset.contents()?;
+        for (asset, body) in self.section_bodies()? {
             let name = asset.git_ignore().get_name();
-            buf.push_str("# [");
-            buf.push_str(name);
-            buf.push_str("]\n");
-            buf.push_str(contents.as_str());
-            buf.push_str("\n\n");
-            nonempty |= true;
-        }
-        let bytes_written = if nonempty {
-            let path = self.get_target_path()?;
-            self.backup_if_exists(&path)?;
-            let total = buf.len();
-            fs::write(path, buf)?;
-            total
-        } else {
-            0
+            if !present.contains(&name) {
+                Self::push_section(&mut buf, asset, &body);
+            }
+        }
+        // keep the appended sections visually separated from whatever
+        // the existing file ends with
+        if !(buf.is_empty() || existing.is_empty()) {
+            if !existing.ends_with('\n') {
+                buf.insert_str(0, "\n\n");
+            } else if !existing.ends_with("\n\n") {
+                buf.insert(0, '\n');
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Appends to the `.gitignore` file in the directory described by
+    /// the `target` field the contents of every `Asset` whose section
+    /// is not already present in that file, returning the number of
+    /// bytes appended. Unlike `FileGen::write_to_target`, the existing
+    /// file is neither backed up nor overwritten.
+    ///
+    /// If the output path does not exist yet, then this behaves
+    /// exactly like `FileGen::write_to_target`.
+    pub fn append_to_target(&self) -> io::Result<usize> {
+        let path = self.get_target_path()?;
+        if !path.exists() {
+            return self.write_to_target();
+        }
+        let existing = fs::read_to_string(&path)?;
+        let buf = self.render_append(&existing)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut file = fs::OpenOptions::new().append(true).open(path)?;
+        file.write_all(buf.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    /// Determines what `FileGen::append_to_target` would do without
+    /// touching the filesystem.
+    pub fn plan_append(&self) -> io::Result<Plan> {
+        let path = self.get_target_path()?;
+        let existing = match fs::read_to_string(&path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return self.plan_from(None),
+            Err(e) => return Err(e),
         };
+        let contents = existing.clone() + &self.render_append(&existing)?;
+        Ok(Plan {
+            path,
+            existing: Some(existing),
+            contents,
+            backup: None,
+        })
+    }
+
+    /// Returns the assets that `FileGen::append_to_target` would skip
+    /// because their sections are already present in `contents`.
+    pub fn present_in<'a>(&'a self, contents: &'a str) -> impl Iterator<Item = &'a Asset> + 'a {
+        let present = Self::section_names(contents);
+        self.assets
+            .iter()
+            .filter(move |asset| present.contains(&asset.git_ignore().get_name()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        assets::{AssetDB, AssetSource},
+        test_util::TempDir,
+    };
+
+    /// Returns a `FileGen` writing to `dir` with the given templates
+    /// from `dir/templates`.
+    fn file_gen(dir: &TempDir, names: &[&str]) -> FileGen {
+        let assets = AssetDB::from_sources(&[AssetSource::Dir(dir.path().join("templates"))]);
+        let assets = names
+            .iter()
+            .map(|name| assets.get_by_name(name).unwrap().clone())
+            .collect::<Vec<_>>();
+        FileGen::with_assets_and_target(assets, dir.path()).unwrap()
+    }
+
+    #[test]
+    fn test_hand_edited_sections() {
+        let dir = TempDir::new();
+        dir.write("templates/Alpha.gitignore", "*.alpha\n");
+        dir.write("templates/Beta.gitignore", "*.beta\n");
+        file_gen(&dir, &["Alpha", "Beta"])
+            .write_to_target()
+            .unwrap();
+        let path = dir.path().join(".gitignore");
+        let generated = fs::read_to_string(&path).unwrap();
+        let edited = generated.replace("*.alpha\n", "*.alpha\n/mine\n");
+        fs::write(&path, &edited).unwrap();
+
+        // the sections of other templates are kept as they are
+        let plan = file_gen(&dir, &["Beta"]).plan().unwrap();
+        assert_eq!(plan.contents, edited);
+        assert_eq!(plan.backup, None);
+
+        // overwriting or removing the edited section keeps a copy
+        let backup = dir.path().join(".gitignore.backup_1");
+        let plan = file_gen(&dir, &["Alpha"]).plan().unwrap();
+        assert_eq!(plan.contents, generated);
+        assert_eq!(plan.backup.as_ref(), Some(&backup));
+        let plan = file_gen(&dir, &["Beta"]).pruning_sections().plan().unwrap();
+        assert!(!plan.contents.contains("alpha"), "{}", plan.contents);
+        assert_eq!(plan.backup.as_ref(), Some(&backup));
+        file_gen(&dir, &["Alpha"]).write_to_target().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), generated);
+        assert_eq!(fs::read_to_string(backup).unwrap(), edited);
+
+        // removing an untouched section loses nothing
+        let plan = file_gen(&dir, &["Beta"]).pruning_sections().plan().unwrap();
+        assert_eq!(plan.backup, None);
+    }
+
+    #[test]
+    fn test_preserving_existing() {
+        let dir = TempDir::new();
+        dir.write("templates/Alpha.gitignore", "*.alpha\n");
+        let path = dir.write(".gitignore", "/mine\n");
+        let plan = file_gen(&dir, &["Alpha"]).plan().unwrap();
+        assert_eq!(plan.backup, Some(dir.path().join(".gitignore.backup_1")));
+
+        // the sections are appended instead
+        let file_gen = file_gen(&dir, &["Alpha"]).preserving_existing();
+        let plan = file_gen.plan().unwrap();
+        assert_eq!(plan.backup, None);
+        assert!(plan
+            .contents
+            .starts_with("/mine\n\n# >>> ignore_me: begin [Alpha.gitignore]"));
+        file_gen.write_plan(&plan).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), plan.contents);
+
+        // ... and regenerated in place from then on
+        let plan = file_gen.plan().unwrap();
+        assert_eq!(plan.contents, plan.existing.unwrap());
+    }
 
-        Ok(bytes_written)
+    #[test]
+    fn test_created_target() {
+        let dir = TempDir::new();
+        dir.write("templates/Alpha.gitignore", "*.alpha\n");
+        let info = dir.path().join("info");
+        let assets = file_gen(&dir, &["Alpha"]).assets;
+        let file_gen = FileGen::with_assets(assets)
+            .with_created_target(&info)
+            .with_filename("exclude")
+            .unwrap();
+        // planning leaves the filesystem alone
+        let plan = file_gen.plan().unwrap();
+        assert_eq!(plan.path, info.join("exclude"));
+        assert!(!info.exists());
+        file_gen.write_plan(&plan).unwrap();
+        assert_eq!(
+            fs::read_to_string(info.join("exclude")).unwrap(),
+            plan.contents
+        );
     }
 }
@@ -0,0 +1,102 @@
+//! Approximate string matching used to suggest (or pick) `.gitignore`
+//! files when a name provided on the command line matches none
+//! exactly, e.g., `pyhton` or `nodejs`.
+
+/// Scores below this are not worth suggesting.
+pub const MIN_SCORE: f64 = 0.5;
+
+/// The score a match must reach to be picked automatically when
+/// fuzzy matching is enabled.
+pub const CONFIDENCE: f64 = 0.8;
+
+/// Returns the optimal string alignment distance between `a` and `b`,
+/// i.e., the number of insertions, deletions, substitutions and
+/// transpositions of adjacent characters needed to turn one into the
+/// other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // rows for `i - 2`, `i - 1` and `i` respectively
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut curr = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                curr[j] = curr[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Scores how well `candidate` matches `query`, ignoring ASCII case,
+/// from `0.0` (nothing in common) to `1.0` (equal). Either string being
+/// a prefix or (if at least three characters long) a substring of the
+/// other scores higher the closer their lengths are; otherwise the
+/// score is based on the edit distance between the two.
+pub fn score(query: &str, candidate: &str) -> f64 {
+    let query = query.trim().to_ascii_lowercase();
+    let candidate = candidate.trim().to_ascii_lowercase();
+    if query.is_empty() || candidate.is_empty() {
+        return 0.0;
+    }
+    if query == candidate {
+        return 1.0;
+    }
+    // lengths are in characters, so that non-ASCII names aren't skewed
+    let (query_len, candidate_len) = (query.chars().count(), candidate.chars().count());
+    let (short, long, short_len, long_len) = if query_len <= candidate_len {
+        (&query, &candidate, query_len, candidate_len)
+    } else {
+        (&candidate, &query, candidate_len, query_len)
+    };
+    let ratio = short_len as f64 / long_len as f64;
+    let by_distance = 1.0 - edit_distance(&query, &candidate) as f64 / long_len as f64;
+    let by_affix = if long.starts_with(short.as_str()) {
+        0.7 + 0.25 * ratio
+    } else if short_len >= 3 && long.contains(short.as_str()) {
+        0.6 + 0.25 * ratio
+    } else {
+        0.0
+    };
+    by_affix.max(by_distance).max(0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("pyhton", "python"), 1);
+        assert_eq!(edit_distance("rust", "rust"), 0);
+    }
+
+    #[test]
+    fn test_score_ranking() {
+        assert_eq!(score("Python", "python"), 1.0);
+        assert!(score("pyhton", "Python") >= CONFIDENCE);
+        assert!(score("nodejs", "Node") > MIN_SCORE);
+        assert!(score("nodejs", "Node") > score("nodejs", "Nim"));
+        assert!(score("jet", "JetBrains") > score("brains", "JetBrains"));
+        assert!(score("haskell", "Elisp") < MIN_SCORE);
+        assert!(score("pyhton", "h") < MIN_SCORE);
+    }
+
+    #[test]
+    fn test_score_counts_characters() {
+        // 3 edits against the 5 characters of the longer name, although
+        // the other one takes more bytes
+        assert!((score("ééab", "eeabc") - 0.4).abs() < 1e-9);
+        assert!((score("eeabc", "ééab") - 0.4).abs() < 1e-9);
+        assert!((score("ñu", "ñuxx") - (0.7 + 0.25 * 0.5)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,509 @@
+//! Minimal access to the local git repository, reading its files
+//! directly so that no git binary is required.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A git repository with a work tree.
+#[derive(Clone, Debug)]
+pub struct Repo {
+    /// The top-level directory of the work tree.
+    pub work_tree: PathBuf,
+    /// The directory holding the repository data of the work tree,
+    /// i.e., usually `<WORK_TREE>/.git`.
+    pub git_dir: PathBuf,
+}
+
+impl Repo {
+    /// Finds the repository whose work tree contains the directory
+    /// `start`, looking for a `.git` directory (or a `.git` file
+    /// pointing to one, as used by worktrees and submodules) in `start`
+    /// and the directories above it. The paths of the repository are
+    /// canonical, as `start` is canonicalized first.
+    pub fn discover(start: &Path) -> io::Result<Option<Repo>> {
+        let start = fs::canonicalize(start)?;
+        for dir in start.ancestors() {
+            let dot_git = dir.join(".git");
+            if dot_git.is_dir() {
+                return Ok(Some(Repo {
+                    work_tree: dir.to_path_buf(),
+                    git_dir: dot_git,
+                }));
+            }
+            if dot_git.is_file() {
+                let contents = fs::read_to_string(&dot_git)?;
+                let git_dir = match contents.trim_end().strip_prefix("gitdir: ") {
+                    Some(path) => dir.join(path),
+                    None => {
+                        return Err(invalid(format!(
+                            "`{}` does not point to a git directory",
+                            dot_git.display()
+                        )))
+                    }
+                };
+                return Ok(Some(Repo {
+                    work_tree: dir.to_path_buf(),
+                    git_dir,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the directory holding the data shared by all work trees
+    /// of the repository, which differs from `git_dir` for linked work
+    /// trees (see `git worktree`).
+    pub fn common_dir(&self) -> io::Result<PathBuf> {
+        match fs::read_to_string(self.git_dir.join("commondir")) {
+            Ok(contents) => fs::canonicalize(self.git_dir.join(contents.trim_end())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(self.git_dir.clone()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the path of the file holding the ignore rules that apply
+    /// to the repository without being committed.
+    pub fn exclude_file(&self) -> io::Result<PathBuf> {
+        Ok(self.common_dir()?.join("info").join("exclude"))
+    }
+
+    /// Returns the paths of the files tracked in the index of the
+    /// repository, relative to the top-level directory of the work
+    /// tree. A repository without an index tracks nothing.
+    pub fn tracked_files(&self) -> io::Result<Vec<PathBuf>> {
+        let bytes = match fs::read(self.git_dir.join("index")) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let hash_len = if self.uses_sha256()? { 32 } else { 20 };
+        Ok(parse_index(&bytes, hash_len)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Returns whether the object names of the repository are SHA-256
+    /// rather than SHA-1 hashes, which changes the layout of the index.
+    /// This is recorded in the config shared by all work trees.
+    fn uses_sha256(&self) -> io::Result<bool> {
+        let config = match fs::read_to_string(self.common_dir()?.join("config")) {
+            Ok(config) => config,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        Ok(config.lines().any(|line| {
+            let line = line.trim().to_lowercase();
+            line.split_once('=').is_some_and(|(key, value)| {
+                key.trim() == "objectformat" && value.trim() == "sha256"
+            })
+        }))
+    }
+}
+
+/// Returns the path of the user's global excludes file, i.e., the value
+/// of `core.excludesFile` in the global git config or else git's
+/// default of `$XDG_CONFIG_HOME/git/ignore`, or `None` if the home
+/// directory is unknown. Files included by the config are not read.
+pub fn global_excludes_file() -> io::Result<Option<PathBuf>> {
+    excludes_file_with(env_path)
+}
+
+/// Like `global_excludes_file`, but with `env` providing the values of
+/// environment variables.
+fn excludes_file_with(env: impl Fn(&str) -> Option<PathBuf>) -> io::Result<Option<PathBuf>> {
+    let home = env("HOME").or_else(|| env("USERPROFILE"));
+    let config_home = match env("XDG_CONFIG_HOME") {
+        Some(dir) => dir,
+        None => match home.as_ref() {
+            Some(home) => home.join(".config"),
+            None => return Ok(None),
+        },
+    };
+    // like git, read `~/.gitconfig` last so that it takes precedence,
+    // unless `GIT_CONFIG_GLOBAL` names another file
+    let files = match env("GIT_CONFIG_GLOBAL") {
+        Some(file) => vec![file],
+        None => [
+            Some(config_home.join("git").join("config")),
+            home.as_ref().map(|home| home.join(".gitconfig")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+    };
+    let mut excludes_file = None;
+    for file in files {
+        let contents = match fs::read_to_string(&file) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(value) = config_value(&contents, "core", "excludesfile") {
+            excludes_file = Some(value);
+        }
+    }
+    Ok(Some(match excludes_file {
+        Some(value) => match (value.strip_prefix("~/"), home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(value),
+        },
+        None => config_home.join("git").join("ignore"),
+    }))
+}
+
+/// Returns the value of the given (non-empty) environment variable as a
+/// path.
+fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the last value of the given key (in lowercase) in the given
+/// section (in lowercase, without a subsection) of a git config file.
+fn config_value(contents: &str, section: &str, key: &str) -> Option<String> {
+    let mut current = String::new();
+    let mut value = None;
+    // a backslash at the end of a line continues it on the next one
+    let contents = contents.replace("\\\r\n", "").replace("\\\n", "");
+    for line in contents.lines() {
+        let mut line = line.trim_start();
+        if let Some(rest) = line.strip_prefix('[') {
+            let (header, rest) = rest.split_once(']').unwrap_or((rest, ""));
+            // sections with a subsection (e.g., `[core "sub"]`) never
+            // match, as they are kept with a trailing space
+            current = match header.split_once(|c: char| c.is_whitespace() || c == '"') {
+                Some((name, _)) => format!("{name} "),
+                None => header.to_string(),
+            }
+            .to_lowercase();
+            line = rest.trim_start();
+        }
+        if line.is_empty() || line.starts_with(['#', ';']) || current != section {
+            continue;
+        }
+        let (name, rest) = match line.split_once('=') {
+            Some((name, rest)) => (name, rest),
+            None => (line, ""),
+        };
+        if name.trim().to_lowercase() == key {
+            value = Some(config_string(rest));
+        }
+    }
+    value
+}
+
+/// Parses the value of a git config entry, which may be (partially)
+/// quoted, contain escape sequences and be followed by a comment.
+fn config_string(raw: &str) -> String {
+    let mut value = String::new();
+    // the length of `value` up to its last character that is quoted or
+    // not whitespace, after which whitespace is trimmed
+    let mut end = 0;
+    let mut quoted = false;
+    let mut chars = raw.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => break,
+            '\\' => {
+                value.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('b') => '\x08',
+                    Some(c) => c,
+                    None => break,
+                });
+                end = value.len();
+            }
+            c => {
+                value.push(c);
+                if quoted || !c.is_whitespace() {
+                    end = value.len();
+                }
+            }
+        }
+    }
+    value.truncate(end);
+    value
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Returns the paths of the entries of the given index file (in version
+/// 2, 3 or 4 of the format), each listed once even if it has several
+/// stages due to a merge conflict. `hash_len` is the length in bytes of
+/// the object names.
+pub fn parse_index(bytes: &[u8], hash_len: usize) -> io::Result<Vec<String>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != b"DIRC" {
+        return Err(invalid("the git index has an invalid signature"));
+    }
+    let version = reader.u32()?;
+    if !(2..=4).contains(&version) {
+        return Err(invalid(format!(
+            "version {version} of the git index is not supported"
+        )));
+    }
+    let count = reader.u32()?;
+    let mut paths: Vec<String> = vec![];
+    let mut previous = Vec::new();
+    for _ in 0..count {
+        let start = reader.pos;
+        // timestamps, device, inode, mode, uid, gid and size, followed
+        // by the object name
+        reader.take(40 + hash_len)?;
+        let flags = reader.u16()?;
+        if version >= 3 && flags & 0x4000 != 0 {
+            reader.u16()?;
+        }
+        let name = if version == 4 {
+            // the name is stored as the number of bytes to remove from
+            // the end of the previous name, followed by what to append
+            let strip = reader.varint()?;
+            let keep = previous
+                .len()
+                .checked_sub(strip)
+                .ok_or_else(|| invalid("the git index has an invalid path"))?;
+            let mut name = previous[..keep].to_vec();
+            name.extend_from_slice(reader.until_nul()?);
+            name
+        } else {
+            let name = reader.until_nul()?.to_vec();
+            // entries are padded with 1 to 8 NUL bytes to a multiple of
+            // 8 bytes
+            let len = reader.pos - start;
+            reader.take((8 - len % 8) % 8)?;
+            name
+        };
+        let path = String::from_utf8_lossy(&name).into_owned();
+        if paths.last() != Some(&path) {
+            paths.push(path);
+        }
+        previous = name;
+    }
+    Ok(paths)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| invalid("the git index is truncated"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads the bytes up to the next NUL byte, consuming it as well.
+    fn until_nul(&mut self) -> io::Result<&'a [u8]> {
+        let rest = &self.bytes[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("the git index is truncated"))?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    /// Reads a variable-length integer as encoded by git, where each
+    /// byte but the last has its high bit set and adds one to the
+    /// value of the following bytes.
+    fn varint(&mut self) -> io::Result<usize> {
+        let mut byte = self.take(1)?[0];
+        let mut value = usize::from(byte & 0x7f);
+        while byte & 0x80 != 0 {
+            byte = self.take(1)?[0];
+            value = ((value + 1) << 7) | usize::from(byte & 0x7f);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// Builds an index of a repository whose object names are
+    /// `hash_len` bytes long, containing entries with the given names,
+    /// stages and (for version 4) number of bytes stripped from the
+    /// previous name.
+    fn index(version: u32, hash_len: usize, entries: &[(&str, u16, u8)]) -> Vec<u8> {
+        let mut bytes = b"DIRC".to_vec();
+        bytes.extend_from_slice(&version.to_be_bytes());
+        bytes.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for &(name, stage, strip) in entries {
+            let start = bytes.len();
+            bytes.resize(bytes.len() + 40 + hash_len, 0);
+            bytes.extend_from_slice(&((stage << 12) | name.len() as u16).to_be_bytes());
+            if version == 4 {
+                bytes.push(strip);
+            }
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+            if version != 4 {
+                while !(bytes.len() - start).is_multiple_of(8) {
+                    bytes.push(0);
+                }
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = TempDir::new();
+        let main = dir.path().join("main");
+        let linked = dir.path().join("linked");
+        fs::create_dir_all(linked.join("src")).unwrap();
+        dir.write(
+            "linked/.git",
+            &format!("gitdir: {}\n", main.join(".git/worktrees/linked").display()),
+        );
+        dir.write("main/.git/worktrees/linked/commondir", "../..\n");
+
+        let repo = Repo::discover(&main).unwrap().unwrap();
+        assert_eq!(repo.git_dir, main.join(".git"));
+        assert_eq!(repo.exclude_file().unwrap(), main.join(".git/info/exclude"));
+
+        let repo = Repo::discover(&linked.join("src")).unwrap().unwrap();
+        assert_eq!(repo.work_tree, linked);
+        assert_eq!(repo.git_dir, main.join(".git/worktrees/linked"));
+        assert_eq!(repo.exclude_file().unwrap(), main.join(".git/info/exclude"));
+    }
+
+    #[test]
+    fn test_tracked_files_of_sha256_work_tree() {
+        let dir = TempDir::new();
+        let main = dir.path().join("main");
+        dir.write(
+            "main/.git/config",
+            "[extensions]\n\tobjectFormat = sha256\n",
+        );
+        dir.write("main/.git/worktrees/linked/commondir", "../..\n");
+        dir.write(
+            "linked/.git",
+            &format!("gitdir: {}\n", main.join(".git/worktrees/linked").display()),
+        );
+        fs::create_dir_all(dir.path().join("linked/src")).unwrap();
+        let bytes = index(2, 32, &[("new.rs", 0, 0), ("src/lib.rs", 0, 0)]);
+        fs::write(main.join(".git/worktrees/linked/index"), bytes).unwrap();
+
+        // the object format is only recorded in the config of `main`
+        let repo = Repo::discover(&dir.path().join("linked/src"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            repo.tracked_files().unwrap(),
+            vec![PathBuf::from("new.rs"), PathBuf::from("src/lib.rs")]
+        );
+    }
+
+    #[test]
+    fn test_config_value() {
+        let contents = "[user]\n\texcludesFile = nope\n\
+                        [core \"sub\"]\n\texcludesFile = nope\n\
+                        [Core]\n\tExcludesFile = ~/first\n\
+                        \t# excludesfile = commented\n\
+                        [core] excludesfile = \"~/My Ignores\" ; comment\n";
+        assert_eq!(
+            config_value(contents, "core", "excludesfile"),
+            Some(String::from("~/My Ignores"))
+        );
+        assert_eq!(config_value(contents, "core", "editor"), None);
+        assert_eq!(config_string("a\\\"b  # c"), "a\"b");
+        assert_eq!(config_string("\" a \"  "), " a ");
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let bytes = index(
+            2,
+            20,
+            &[
+                (".idea/codeStyles/Project.xml", 0, 0),
+                ("src/main.rs", 1, 0),
+                ("src/main.rs", 2, 0),
+                ("src/main.rsx", 0, 0),
+            ],
+        );
+        assert_eq!(
+            parse_index(&bytes, 20).unwrap(),
+            vec![
+                ".idea/codeStyles/Project.xml",
+                "src/main.rs",
+                "src/main.rsx"
+            ]
+        );
+        assert!(parse_index(&bytes[..bytes.len() - 8], 20).is_err());
+    }
+
+    #[test]
+    fn test_parse_index_v4() {
+        let bytes = index(
+            4,
+            20,
+            &[("src/lib.rs", 0, 0), ("main.rs", 0, 6), ("x", 0, 7)],
+        );
+        assert_eq!(
+            parse_index(&bytes, 20).unwrap(),
+            vec!["src/lib.rs", "src/main.rs", "src/x"]
+        );
+    }
+
+    #[test]
+    fn test_global_excludes_file() {
+        let dir = TempDir::new();
+        let home = dir.path().join("home");
+        let config_home = dir.path().join("config");
+        let excludes_file = |vars: &[(&str, &Path)]| {
+            excludes_file_with(|var| {
+                vars.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_path_buf())
+            })
+            .unwrap()
+        };
+        let vars = [("HOME", home.as_path()), ("XDG_CONFIG_HOME", &config_home)];
+        assert_eq!(excludes_file(&[]), None);
+        assert_eq!(excludes_file(&vars), Some(config_home.join("git/ignore")));
+        assert_eq!(
+            excludes_file(&vars[..1]),
+            Some(home.join(".config/git/ignore"))
+        );
+
+        // `~/.gitconfig` is read after the XDG config and wins
+        dir.write("config/git/config", "[core]\n\texcludesFile = ~/xdg\n");
+        assert_eq!(excludes_file(&vars), Some(home.join("xdg")));
+        dir.write("home/.gitconfig", "[core]\n\texcludesFile = /abs/ignore\n");
+        assert_eq!(excludes_file(&vars), Some(PathBuf::from("/abs/ignore")));
+        dir.write("home/.gitconfig", "[user]\n\tname = me\n");
+        assert_eq!(excludes_file(&vars), Some(home.join("xdg")));
+
+        // unless another file is given
+        let other = dir.write("other", "[core]\n\texcludesFile = ~/other\n");
+        let vars = [vars[0], vars[1], ("GIT_CONFIG_GLOBAL", &other)];
+        assert_eq!(excludes_file(&vars), Some(home.join("other")));
+    }
+}
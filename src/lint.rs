@@ -0,0 +1,281 @@
+//! Detection of common problems in `.gitignore` files, many of which
+//! arise when combining several templates.
+
+use std::{fmt, path::PathBuf};
+
+use crate::{
+    matcher::Matcher,
+    pattern::{Kind, Line, PatternError, Rule, Segment, Token},
+};
+
+/// A problem found on a line.
+#[derive(Clone, Debug)]
+pub struct Issue<'a> {
+    pub line: &'a Line,
+    pub kind: IssueKind<'a>,
+}
+
+#[derive(Clone, Debug)]
+pub enum IssueKind<'a> {
+    /// The rule repeats the given earlier rule with no rule of the
+    /// opposite polarity in between, so removing it changes nothing.
+    Duplicate(&'a Line),
+    /// The rule never decides whether a path is ignored, since the
+    /// given later rule matches everything it matches.
+    Shadowed(&'a Line),
+    /// The negated rule can never re-include anything, since the given
+    /// directory containing everything it matches is ignored by the
+    /// given rule.
+    ExcludedParent(PathBuf, &'a Line),
+    /// The line ends with unescaped spaces, which git ignores.
+    TrailingSpace,
+    /// The line ends with a tab, which git considers part of the
+    /// pattern.
+    TrailingTab,
+    Invalid(PatternError),
+}
+
+impl fmt::Display for Issue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: `{}` ", self.line.span, self.line.text)?;
+        match &self.kind {
+            IssueKind::Duplicate(other) => {
+                write!(f, "duplicates `{}` ({})", other.text.trim_end(), other.span)
+            }
+            IssueKind::Shadowed(other) => write!(
+                f,
+                "never applies, as the later `{}` ({}) matches everything it does",
+                other.text.trim_end(),
+                other.span
+            ),
+            IssueKind::ExcludedParent(parent, other) => write!(
+                f,
+                "can never take effect, as `{}/` is ignored by `{}` ({})",
+                parent.display(),
+                other.text.trim_end(),
+                other.span
+            ),
+            IssueKind::TrailingSpace => {
+                write!(f, "ends with spaces, which are ignored unless escaped")
+            }
+            IssueKind::TrailingTab => {
+                write!(f, "ends with a tab, which is part of the pattern")
+            }
+            IssueKind::Invalid(e) => write!(f, "never matches, as {e}"),
+        }
+    }
+}
+
+/// Returns the problems found in the given lines of a `.gitignore`
+/// file, in the order of the lines they were found on.
+pub fn lint(lines: &[Line]) -> Vec<Issue<'_>> {
+    let mut issues = vec![];
+    // rules are compared by their normalized form, rendered once here
+    let rules = lines
+        .iter()
+        .filter_map(|line| line.rule().map(|rule| (line, rule, rule.to_string())))
+        .collect::<Vec<_>>();
+    let mut matcher = Matcher::new();
+    matcher.add("", lines.to_vec());
+    for line in lines {
+        let mut push = |kind| issues.push(Issue { line, kind });
+        if let Kind::Invalid(e) = line.kind {
+            push(IssueKind::Invalid(e));
+        }
+        if line.rule().is_some() {
+            if line.text.ends_with('\t') {
+                push(IssueKind::TrailingTab);
+            } else if ends_with_unescaped_space(&line.text) {
+                push(IssueKind::TrailingSpace);
+            }
+        }
+    }
+    for (n, &(line, rule, ref normalized)) in rules.iter().enumerate() {
+        let later = &rules[n + 1..];
+        // the nearest earlier occurrence of the same rule
+        let previous = rules[..n]
+            .iter()
+            .rposition(|(_, _, other)| other == normalized);
+        if let Some(p) = previous {
+            let between = &rules[p + 1..n];
+            if between
+                .iter()
+                .all(|(_, other, _)| other.negated == rule.negated)
+            {
+                issues.push(Issue {
+                    line,
+                    kind: IssueKind::Duplicate(rules[p].0),
+                });
+            }
+        }
+        let shadowing = later.iter().find(|&&(_, other, ref rendered)| {
+            let identical = rendered == normalized;
+            // an identical rule following without a rule of the opposite
+            // polarity in between is reported as a duplicate instead
+            covers(other, rule)
+                && !(identical
+                    && later
+                        .iter()
+                        .take_while(|(_, r, _)| !std::ptr::eq(*r, other))
+                        .all(|(_, r, _)| r.negated == rule.negated))
+        });
+        if let Some(&(other_line, _, _)) = shadowing {
+            issues.push(Issue {
+                line,
+                kind: IssueKind::Shadowed(other_line),
+            });
+        }
+        if rule.negated {
+            if let Some(parent) = literal_parent(rule) {
+                let verdict = matcher.check(&parent, true);
+                // the matcher holds copies of the lines
+                let other = verdict
+                    .rule
+                    .and_then(|other| lines.iter().find(|l| l.span == other.span));
+                if let (true, Some(other)) = (verdict.ignored, other) {
+                    issues.push(Issue {
+                        line,
+                        kind: IssueKind::ExcludedParent(verdict.parent.unwrap_or(parent), other),
+                    });
+                }
+            }
+        }
+    }
+    let position = |line: &Line| lines.iter().position(|l| std::ptr::eq(l, line));
+    issues.sort_by_key(|issue| position(issue.line));
+    issues
+}
+
+/// Returns whether the given line ends with a space that is not escaped
+/// by a backslash.
+fn ends_with_unescaped_space(text: &str) -> bool {
+    match text.strip_suffix(' ') {
+        Some(rest) => rest.chars().rev().take_while(|&c| c == '\\').count() % 2 == 0,
+        None => false,
+    }
+}
+
+/// Returns the directory containing everything the given anchored rule
+/// matches, if all of its segments but the last are literal.
+fn literal_parent(rule: &Rule) -> Option<PathBuf> {
+    if !rule.anchored || rule.segments.len() < 2 {
+        return None;
+    }
+    let mut parent = PathBuf::new();
+    for segment in &rule.segments[..rule.segments.len() - 1] {
+        let name = match segment {
+            Segment::Glob(tokens) => tokens
+                .iter()
+                .map(|token| match token {
+                    Token::Char(c) => Some(*c),
+                    _ => None,
+                })
+                .collect::<Option<String>>()?,
+            Segment::AnyDirs => return None,
+        };
+        parent.push(name);
+    }
+    Some(parent)
+}
+
+/// Returns whether `b` matches every path `a` matches. This errs on the
+/// side of returning `false` when that can't easily be determined.
+pub fn covers(b: &Rule, a: &Rule) -> bool {
+    if b.dir_only && !a.dir_only {
+        return false;
+    }
+    match (b.anchored, b.segments.as_slice(), a.segments.last()) {
+        // a rule matching names at any depth matches everything whose
+        // last component it matches
+        (false, [Segment::Glob(b)], Some(Segment::Glob(a))) => glob_covers(b, a),
+        _ => {
+            b.anchored == a.anchored
+                && b.segments.len() == a.segments.len()
+                && b.segments
+                    .iter()
+                    .zip(a.segments.iter())
+                    .all(|pair| match pair {
+                        (Segment::Glob(b), Segment::Glob(a)) => glob_covers(b, a),
+                        (b, a) => b == a,
+                    })
+        }
+    }
+}
+
+/// Returns whether the glob `b` matches every name the glob `a` matches.
+fn glob_covers(b: &[Token], a: &[Token]) -> bool {
+    match (b.split_first(), a.split_first()) {
+        (None, None) => true,
+        (Some((Token::Star, rest)), _) => (0..=a.len()).any(|i| glob_covers(rest, &a[i..])),
+        (None, Some(_)) | (Some(_), None) => false,
+        (Some((b_token, b_rest)), Some((a_token, a_rest))) => {
+            let covered = match (b_token, a_token) {
+                (_, Token::Star) => false,
+                (Token::AnyChar, _) => true,
+                (Token::Char(b), Token::Char(a)) => b == a,
+                (Token::Class { negated, ranges }, Token::Char(a)) => {
+                    ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(a)) != *negated
+                }
+                (b, a) => b == a,
+            };
+            covered && glob_covers(b_rest, a_rest)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::pattern::{self, Source};
+
+    fn kinds(contents: &str) -> Vec<(usize, String)> {
+        let lines = pattern::parse(contents, &Rc::new(Source::file(".gitignore")));
+        lint(&lines)
+            .into_iter()
+            .map(|issue| {
+                let kind = format!("{:?}", issue.kind);
+                let kind = kind.split('(').next().unwrap_or_default().to_string();
+                (issue.line.span.line, kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let contents = "*.o\nfoo.o\n*.o\nbuild/\n!build/keep.txt\n*.log \n[abc\n";
+        assert_eq!(
+            kinds(contents),
+            vec![
+                (2, "Shadowed".to_string()),
+                (3, "Duplicate".to_string()),
+                (5, "ExcludedParent".to_string()),
+                (6, "TrailingSpace".to_string()),
+                (7, "Invalid".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_respects_negations() {
+        // the second `*.log` re-ignores `keep.log`, so it isn't a
+        // duplicate, but neither the first one nor the negation applies
+        let contents = "*.log\n!keep.log\n*.log\n";
+        assert_eq!(
+            kinds(contents),
+            vec![(1, "Shadowed".to_string()), (2, "Shadowed".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_covers() {
+        let rule = |text| pattern::parse_pattern(text).unwrap().unwrap();
+        assert!(covers(&rule("*.o"), &rule("/src/foo.o")));
+        assert!(covers(&rule("*"), &rule("a/b/")));
+        assert!(!covers(&rule("*.o/"), &rule("foo.o")));
+        assert!(covers(&rule("/a/*"), &rule("a/[bc]")));
+        assert!(!covers(&rule("/a/b"), &rule("a/*")));
+        assert!(!covers(&rule("a?"), &rule("a*")));
+    }
+}
@@ -1,71 +1,1328 @@
-#![allow(unused)]
-
 mod args;
 mod assets;
 mod data;
+mod dedupe;
+mod detect;
+mod diff;
 mod file_gen;
+mod fuzzy;
+mod git;
+mod lint;
+mod managed;
+mod matcher;
+mod pattern;
 mod term;
+#[cfg(test)]
+mod test_util;
+
+use std::process::ExitCode;
+
+use args::{Commands, FlagArg, GenCommand, GenOpts, NameOpts, TargetOpts};
+use assets::{Asset, AssetDB, Flag};
+use file_gen::{FileGen, Plan};
+use term::Term;
 
-use args::{Cli, Commands};
-use assets::AssetDB;
-use file_gen::FileGen;
+/// Exit status of a command that found differences or problems (see
+/// `diff`, `check` and `lint`), or ignored paths (see `test`).
+const EXIT_DIFFERENT: u8 = 1;
 
-fn main() -> std::io::Result<()> {
+/// Exit status of a command generating `.gitignore` content that was
+/// aborted, e.g., because no `.gitignore` file was found.
+const EXIT_ABORTED: u8 = 2;
+
+fn main() -> std::io::Result<ExitCode> {
     let cli = args::Cli::get();
     let debug = cli.debug();
     if debug {
-        println!("[DEBUG] debug mode activated");
+        eprintln!("[DEBUG] debug mode activated");
     }
     match cli.command() {
         Some(cmd) => match cmd {
-            Commands::Add { .. } => {
-                eprintln!("The command `add` is not yet implemented :(")
+            Commands::Gen(cmd) => handle_gen(debug, cmd, false),
+            Commands::Diff { command } => handle_gen(debug, command, true),
+            Commands::Show {
+                names,
+                name_opts,
+                pager,
+            } => {
+                handle_show(debug, names, name_opts, *pager)?;
+                Ok(ExitCode::SUCCESS)
+            }
+            Commands::Check { target, dedupe } => handle_check(debug, &target.into(), *dedupe),
+            Commands::Test {
+                paths,
+                names,
+                name_opts,
+                target,
+            } => handle_test(debug, paths, names, name_opts, &target.into()),
+            Commands::Explain {
+                path,
+                names,
+                name_opts,
+                target,
+            } => handle_explain(debug, path, names, name_opts, &target.into()),
+            Commands::Global {
+                names,
+                name_opts,
+                dry_run,
+                diff,
+            } => {
+                let opts = GenOpts {
+                    dry_run: *dry_run,
+                    diff: *diff,
+                    ..GenOpts::default()
+                };
+                handle_global(debug, names, name_opts, &opts)
             }
-            Commands::Terms { .. } => {
-                eprintln!("The command `terms` is not yet implemented :(")
+            Commands::Lint {
+                file,
+                names,
+                name_opts,
+            } => handle_lint(debug, file.as_deref(), names, name_opts),
+            Commands::List { flag, grep, terms } => {
+                handle_list(*flag, grep.as_deref(), *terms);
+                Ok(ExitCode::SUCCESS)
             }
-            Commands::For { names } => handle_for(debug, names)?,
         },
-        None => {
-            eprintln!("Functionality handling empty command is not yet implemented :(")
-        }
-    };
-    Ok(())
+        None => handle_detect(debug),
+    }
+}
+
+/// Runs the given generation command, only printing a diff of what it
+/// would write if `diff` is set.
+fn handle_gen(debug: bool, cmd: &GenCommand, diff: bool) -> std::io::Result<ExitCode> {
+    let mut opts = cmd.opts().clone();
+    opts.diff |= diff;
+    match cmd {
+        GenCommand::Add {
+            terms,
+            names,
+            name_opts,
+            ..
+        } => handle_add(debug, terms, names, name_opts, &opts),
+        GenCommand::Terms { terms, prune, .. } => handle_terms(debug, terms, *prune, &opts),
+        GenCommand::For {
+            names,
+            name_opts,
+            prune,
+            ..
+        } => handle_for(debug, names, name_opts, *prune, &opts),
+        GenCommand::Update { .. } => handle_update(debug, &opts),
+    }
 }
 
-fn handle_for(debug: bool, names: &[String]) -> std::io::Result<()> {
+fn handle_for(
+    debug: bool,
+    names: &[String],
+    name_opts: &NameOpts,
+    prune: bool,
+    opts: &GenOpts,
+) -> std::io::Result<ExitCode> {
     if names.is_empty() {
         eprintln!("No names provided. Aborting...");
-        return Ok(());
+        return Ok(ExitCode::from(EXIT_ABORTED));
     }
     if debug {
-        println!("[DEBUG] searching `.gitignore` files for...");
+        eprintln!("[DEBUG] searching `.gitignore` files for...");
         for name in names.iter() {
-            println!("    {name}");
+            eprintln!("    {name}");
         }
     }
-    let asset_db = AssetDB::new();
-    let assets = asset_db.get_by_names(names).cloned().collect::<Vec<_>>();
+    let asset_db = AssetDB::new_decorated();
+    let assets = match resolve_names(&asset_db, names, name_opts) {
+        Some(assets) => assets,
+        None => return Ok(ExitCode::from(EXIT_ABORTED)),
+    };
     if assets.is_empty() {
         eprintln!("No assets were found for the following names: ");
         for name in names.iter() {
             eprintln!("    {name}")
         }
         eprintln!("Aborting...");
-        return Ok(());
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    generate(debug, assets, prune, opts)
+}
+
+fn handle_terms(
+    debug: bool,
+    terms: &[String],
+    prune: bool,
+    opts: &GenOpts,
+) -> std::io::Result<ExitCode> {
+    if terms.is_empty() {
+        eprintln!("No terms provided. Aborting...");
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    if debug {
+        eprintln!("[DEBUG] searching `.gitignore` files for terms...");
+        for term in terms.iter() {
+            eprintln!("    {term}");
+        }
+    }
+    let asset_db = AssetDB::new_decorated();
+    let terms = Term::intern_iter(terms).collect::<Vec<_>>();
+    let mut assets = asset_db
+        .filter_by_terms(&terms[..])
+        .cloned()
+        .collect::<Vec<_>>();
+    if assets.is_empty() {
+        eprintln!("No assets were found for the following terms: ");
+        for term in terms.iter() {
+            eprintln!("    {term}")
+        }
+        eprintln!("Aborting...");
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    // the asset database is unordered, so we sort by name to keep the
+    // generated file stable across runs
+    assets.sort_by(|a, b| a.git_ignore().get_name().cmp(b.git_ignore().get_name()));
+    generate(debug, assets, prune, opts)
+}
+
+fn handle_add(
+    debug: bool,
+    terms: &[String],
+    names: &[String],
+    name_opts: &NameOpts,
+    opts: &GenOpts,
+) -> std::io::Result<ExitCode> {
+    if terms.is_empty() && names.is_empty() {
+        eprintln!("No names or terms provided. Aborting...");
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    if debug {
+        eprintln!("[DEBUG] searching `.gitignore` files for...");
+        for name in names.iter() {
+            eprintln!("    {name}");
+        }
+        for term in terms.iter() {
+            eprintln!("    {term} (term)");
+        }
+    }
+    let asset_db = AssetDB::new_decorated();
+    let terms = Term::intern_iter(terms).collect::<Vec<_>>();
+    let mut by_terms = asset_db
+        .filter_by_terms(&terms[..])
+        .cloned()
+        .collect::<Vec<_>>();
+    by_terms.sort_by(|a, b| a.git_ignore().get_name().cmp(b.git_ignore().get_name()));
+    let by_names = match resolve_names(&asset_db, names, name_opts) {
+        Some(assets) => assets,
+        None => return Ok(ExitCode::from(EXIT_ABORTED)),
+    };
+    let assets = by_names.into_iter().chain(by_terms).collect::<Vec<_>>();
+    if assets.is_empty() {
+        eprintln!("No assets were found for the following names and terms: ");
+        for name in names.iter() {
+            eprintln!("    {name}")
+        }
+        for term in terms.iter() {
+            eprintln!("    {term} (term)")
+        }
+        eprintln!("Aborting...");
+        return Ok(ExitCode::from(EXIT_ABORTED));
     }
     if debug {
-        println!("[DEBUG] found `.gitignore` files for...");
+        eprintln!("[DEBUG] found `.gitignore` files for...");
         for asset in assets.iter() {
-            println!("    {asset}");
+            eprintln!("    {asset}");
+        }
+    }
+    let file_gen = match file_gen(assets, &opts.target, opts.dedupe) {
+        Ok(file_gen) => file_gen,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    let path = file_gen.get_target_path()?;
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        for asset in file_gen.present_in(&existing) {
+            print_status(
+                opts,
+                &format!(
+                    "Skipping `{}`, which is already present in `{}`",
+                    asset.git_ignore().get_name(),
+                    path.display()
+                ),
+            );
+        }
+    }
+    if opts.check_tracked || opts.preview_ignored || opts.wants_diff() {
+        let plan = file_gen.plan_append()?;
+        if opts.check_tracked {
+            warn_tracked(&plan, opts)?;
+        }
+        if opts.preview_ignored {
+            preview_ignored(&plan, opts)?;
+        }
+        if opts.wants_diff() {
+            return report_diff(&plan, opts.by_section);
+        }
+    }
+    if opts.dry_run {
+        let buf = match std::fs::read_to_string(&path) {
+            Ok(existing) => file_gen.render_append(&existing)?,
+            Err(_) => file_gen.render()?,
+        };
+        print_stdout(&buf)?;
+        if path.exists() {
+            eprintln!(
+                "Dry run: {} bytes would be added to `{}`",
+                buf.len(),
+                path.display()
+            );
+        } else {
+            eprintln!(
+                "Dry run: {} bytes would be written to `{}`",
+                buf.len(),
+                path.display()
+            );
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let bytes_written = file_gen.append_to_target()?;
+    if bytes_written == 0 {
+        println!("Nothing to add to `{}`", path.display());
+    } else {
+        println!(
+            "Success! {} bytes were added to `{}`",
+            bytes_written,
+            path.display()
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_detect(debug: bool) -> std::io::Result<ExitCode> {
+    let cwd = std::env::current_dir()?;
+    if debug {
+        eprintln!("[DEBUG] detecting project type in `{}`", cwd.display());
+    }
+    let asset_db = AssetDB::new_decorated();
+    let candidates = detect::detect(&asset_db, &cwd)?;
+    if debug {
+        eprintln!("[DEBUG] ranked candidates...");
+        for candidate in candidates.iter() {
+            eprintln!("    {candidate}");
+        }
+    }
+    let matches = detect::top_matches(&candidates);
+    if matches.is_empty() {
+        eprintln!(
+            "Unable to detect the project type in `{}`. Try using `for` or `terms` instead.",
+            cwd.display()
+        );
+        eprintln!("Aborting...");
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    println!("Detected the following `.gitignore` files: ");
+    for candidate in matches.iter() {
+        println!("    {candidate}");
+    }
+    generate(
+        debug,
+        matches.into_iter().map(|c| c.asset.clone()).collect(),
+        false,
+        &GenOpts::default(),
+    )
+}
+
+fn handle_update(debug: bool, opts: &GenOpts) -> std::io::Result<ExitCode> {
+    let asset_db = AssetDB::new();
+    let target = match file_gen(vec![], &opts.target, false) {
+        Ok(file_gen) => file_gen.get_target_path()?,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    let existing = match std::fs::read_to_string(&target) {
+        Ok(existing) => existing,
+        Err(e) => {
+            eprintln!("Unable to read `{}`: {e}. Aborting...", target.display());
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    // files generated before sections were managed only have headers,
+    // so their sections are wrapped in markers first
+    let adopted = managed::adopt_legacy(&existing);
+    let contents = adopted.as_deref().unwrap_or(&existing);
+    let sections = managed::sections(contents);
+    if sections.is_empty() {
+        eprintln!(
+            "No generated sections were found in `{}`. Aborting...",
+            target.display()
+        );
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    let assets = sections
+        .iter()
+        .map(|section| asset_db.get_by_file_name(section.name, Flag::parse(section.flag).as_ref()))
+        .collect::<Vec<_>>();
+    let file_gen = match file_gen(
+        assets
+            .iter()
+            .flatten()
+            .map(|&asset| asset.clone())
+            .collect(),
+        &opts.target,
+        opts.dedupe,
+    ) {
+        Ok(file_gen) => file_gen,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    for (section, asset) in sections.iter().zip(assets) {
+        match asset {
+            Some(asset) => {
+                let status =
+                    if expected_body(&file_gen, section.name)?.as_deref() == Some(section.body) {
+                        "up to date"
+                    } else {
+                        "updated"
+                    };
+                if debug {
+                    eprintln!("[DEBUG] {} <- {asset}", section.name);
+                }
+                print_status(opts, &format!("    {}: {status}", section.name));
+            }
+            None => print_status(
+                opts,
+                &format!(
+                    "    {}: no longer available, leaving the section as is",
+                    section.name
+                ),
+            ),
+        }
+    }
+    let mut plan = file_gen.plan_from(Some(contents.to_string()))?;
+    if adopted.is_some() {
+        // legacy sections may have swallowed rules added by hand below
+        // them, so keep a copy of the original
+        plan.backup = FileGen::backup_path(&plan.path);
+        plan.existing = Some(existing.clone());
+    }
+    if opts.check_tracked {
+        warn_tracked(&plan, opts)?;
+    }
+    if opts.preview_ignored {
+        preview_ignored(&plan, opts)?;
+    }
+    if opts.wants_diff() {
+        return report_diff(&plan, opts.by_section);
+    }
+    if opts.dry_run {
+        report_dry_run(&plan)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if plan.existing.as_deref() == Some(plan.contents.as_str()) {
+        println!("`{}` is already up to date", plan.path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    let bytes_written = file_gen.write_plan(&plan)?;
+    println!(
+        "Success! {} bytes were written to `{}`",
+        bytes_written,
+        plan.path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_check(debug: bool, opts: &TargetOpts, dedupe: bool) -> std::io::Result<ExitCode> {
+    let asset_db = AssetDB::new();
+    let target = match file_gen(vec![], opts, false) {
+        Ok(file_gen) => file_gen.get_target_path()?,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    let existing = match std::fs::read_to_string(&target) {
+        Ok(existing) => existing,
+        Err(e) => {
+            eprintln!("Unable to read `{}`: {e}. Aborting...", target.display());
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    // legacy sections have no recorded hash, so they can only be
+    // checked against their templates
+    let adopted = managed::adopt_legacy(&existing);
+    let contents = adopted.as_deref().unwrap_or(&existing);
+    let sections = managed::sections(contents);
+    let unterminated = managed::unterminated(contents);
+    if sections.is_empty() && unterminated.is_empty() {
+        eprintln!(
+            "No generated sections were found in `{}`. Aborting...",
+            target.display()
+        );
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    let assets = sections
+        .iter()
+        .map(|section| asset_db.get_by_file_name(section.name, Flag::parse(section.flag).as_ref()))
+        .collect::<Vec<_>>();
+    let file_gen = match file_gen(
+        assets
+            .iter()
+            .flatten()
+            .map(|&asset| asset.clone())
+            .collect(),
+        opts,
+        dedupe,
+    ) {
+        Ok(file_gen) => file_gen,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    println!("Checking `{}`...", target.display());
+    let mut problems = 0;
+    for (section, asset) in sections.iter().zip(assets) {
+        let status = match asset {
+            Some(asset) => {
+                if debug {
+                    eprintln!("[DEBUG] {} <- {asset}", section.name);
+                }
+                if expected_body(&file_gen, section.name)?.as_deref() == Some(section.body) {
+                    "up to date"
+                } else if section.is_modified() {
+                    "edited by hand"
+                } else {
+                    "out of date, the template has changed"
+                }
+            }
+            None => "the template is no longer available",
+        };
+        if status != "up to date" {
+            problems += 1;
+        }
+        println!("    {} ({}): {status}", section.name, section.flag);
+    }
+    for name in unterminated {
+        problems += 1;
+        println!("    {name}: the end marker is missing");
+    }
+    if problems == 0 {
+        println!("`{}` is up to date", target.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!(
+        "{problems} problem(s) found in `{}` (see `ignore_me diff update`)",
+        target.display()
+    );
+    if !dedupe
+        && contents
+            .lines()
+            .any(|line| line.starts_with(dedupe::ALSO_IN))
+    {
+        println!("Note: the file seems to have been generated with `--dedupe`, try passing it here as well");
+    }
+    Ok(ExitCode::from(EXIT_DIFFERENT))
+}
+
+fn handle_test(
+    debug: bool,
+    paths: &[std::path::PathBuf],
+    names: &[String],
+    name_opts: &NameOpts,
+    opts: &TargetOpts,
+) -> std::io::Result<ExitCode> {
+    let (matcher, root) = match load_matcher(debug, names, name_opts, opts)? {
+        Some(loaded) => loaded,
+        None => return Ok(ExitCode::from(EXIT_ABORTED)),
+    };
+    let cwd = std::env::current_dir()?;
+    let mut any_ignored = false;
+    for path in paths {
+        let rel = match matcher::relative_path(&root, &cwd.join(path)) {
+            Some(rel) => rel,
+            None => {
+                eprintln!(
+                    "Skipping `{}`, which is outside of `{}`",
+                    path.display(),
+                    root.display()
+                );
+                continue;
+            }
+        };
+        let is_dir = root.join(&rel).is_dir() || path.to_string_lossy().ends_with('/');
+        let verdict = matcher.check(&rel, is_dir);
+        any_ignored |= verdict.ignored;
+        println!("{verdict}");
+    }
+    if any_ignored {
+        Ok(ExitCode::from(EXIT_DIFFERENT))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn handle_explain(
+    debug: bool,
+    path: &std::path::Path,
+    names: &[String],
+    name_opts: &NameOpts,
+    opts: &TargetOpts,
+) -> std::io::Result<ExitCode> {
+    let (matcher, root) = match load_matcher(debug, names, name_opts, opts)? {
+        Some(loaded) => loaded,
+        None => return Ok(ExitCode::from(EXIT_ABORTED)),
+    };
+    let rel = match matcher::relative_path(&root, &std::env::current_dir()?.join(path)) {
+        Some(rel) => rel,
+        None => {
+            eprintln!(
+                "`{}` is outside of `{}`. Aborting...",
+                path.display(),
+                root.display()
+            );
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    let is_dir = root.join(&rel).is_dir() || path.to_string_lossy().ends_with('/');
+    let verdict = matcher.check(&rel, is_dir);
+    let matches = matcher.explain(&rel, is_dir);
+    if matches.is_empty() {
+        println!("No rules match `{}`", rel.display());
+    } else {
+        println!("Rules matching `{}`, in evaluation order: ", rel.display());
+        for (i, (matched, line)) in matches.iter().enumerate() {
+            let wins = verdict.rule.is_some_and(|rule| std::ptr::eq(rule, *line));
+            println!(
+                "    {}. `{}` ({}) matches `{}`{}",
+                i + 1,
+                line.text.trim_end(),
+                line.span,
+                matched.display(),
+                if wins { " <- decides" } else { "" }
+            );
         }
     }
-    let file_gen = FileGen::with_assets(assets);
-    let bytes_written = file_gen.write_to_target()?;
+    println!("{verdict}");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Installs the `.gitignore` files with the given names into the global
+/// excludes file of git (see `git::global_excludes_file`), or without
+/// names refreshes the sections already present in it. Everything else
+/// in the file is kept, and it is only backed up if a section edited by
+/// hand is overwritten.
+fn handle_global(
+    debug: bool,
+    names: &[String],
+    name_opts: &NameOpts,
+    opts: &GenOpts,
+) -> std::io::Result<ExitCode> {
+    let path = match git::global_excludes_file() {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            eprintln!("Unable to locate the home directory. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+        Err(e) => {
+            eprintln!("Unable to read the global git config: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    if debug {
+        eprintln!("[DEBUG] global excludes file: `{}`", path.display());
+    }
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Unable to read `{}`: {e}. Aborting...", path.display());
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    let asset_db = AssetDB::new_decorated();
+    let assets = if names.is_empty() {
+        let contents = existing.as_deref().unwrap_or_default();
+        let adopted = managed::adopt_legacy(contents);
+        managed::sections(adopted.as_deref().unwrap_or(contents))
+            .iter()
+            .filter_map(|section| {
+                asset_db.get_by_file_name(section.name, Flag::parse(section.flag).as_ref())
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    } else {
+        match resolve_names(&asset_db, names, name_opts) {
+            Some(assets) => assets,
+            None => return Ok(ExitCode::from(EXIT_ABORTED)),
+        }
+    };
+    if assets.is_empty() {
+        eprintln!(
+            "No `.gitignore` files to install into `{}`. Aborting...",
+            path.display()
+        );
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+    for asset in assets.iter() {
+        if debug {
+            eprintln!("[DEBUG] installing {asset}");
+        }
+        if asset.git_ignore().get_flag() != &assets::Flag::Global {
+            print_status(
+                opts,
+                &format!(
+                    "Note: `{}` is not a global `.gitignore` file",
+                    asset.git_ignore().get_name()
+                ),
+            );
+        }
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::env::current_dir()?,
+    };
+    // git reads the default location without its directory having to
+    // exist, so it is created when writing
+    let file_gen = FileGen::with_assets(assets)
+        .with_created_target(&dir)
+        .with_filename(path.file_name().unwrap_or_default())?
+        .preserving_existing();
+    let plan = file_gen.plan_from(existing)?;
+    if opts.wants_diff() {
+        return report_diff(&plan, false);
+    }
+    if opts.dry_run {
+        report_dry_run(&plan)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if plan.existing.as_deref() == Some(plan.contents.as_str()) {
+        println!("`{}` is already up to date", plan.path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    let bytes_written = file_gen.write_plan(&plan)?;
     println!(
         "Success! {} bytes were written to `{}`",
         bytes_written,
-        file_gen.get_target_path()?.display()
+        plan.path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_lint(
+    debug: bool,
+    file: Option<&std::path::Path>,
+    names: &[String],
+    name_opts: &NameOpts,
+) -> std::io::Result<ExitCode> {
+    let lines = if names.is_empty() {
+        let path = file.unwrap_or(std::path::Path::new(matcher::Matcher::FILENAME));
+        match matcher::read_file(path, path) {
+            Ok(lines) => lines,
+            Err(e) => {
+                eprintln!("Unable to read `{}`: {e}. Aborting...", path.display());
+                return Ok(ExitCode::from(EXIT_ABORTED));
+            }
+        }
+    } else {
+        let asset_db = AssetDB::new_decorated();
+        let assets = match resolve_names(&asset_db, names, name_opts) {
+            Some(assets) if !assets.is_empty() => assets,
+            _ => {
+                eprintln!("No `.gitignore` files to check. Aborting...");
+                return Ok(ExitCode::from(EXIT_ABORTED));
+            }
+        };
+        let mut lines = vec![];
+        for asset in assets.iter() {
+            if debug {
+                eprintln!("[DEBUG] checking {asset}");
+            }
+            lines.extend(pattern::parse_asset(asset)?);
+        }
+        lines
+    };
+    let issues = lint::lint(&lines);
+    for issue in issues.iter() {
+        println!("{issue}");
+    }
+    if issues.is_empty() {
+        println!("No problems found");
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("{} problem(s) found", issues.len());
+    Ok(ExitCode::from(EXIT_DIFFERENT))
+}
+
+/// Loads the rules applying to the output location described by the
+/// given options: those of the `.gitignore` files with the given names
+/// (or, if there are none, of the existing output file), followed by
+/// those of the `.gitignore` files taking precedence over them (see
+/// `add_gitignore_files`). Returns the matcher along with the
+/// (canonical) directory the rules apply to, or `None` after reporting
+/// why the rules could not be loaded.
+fn load_matcher(
+    debug: bool,
+    names: &[String],
+    name_opts: &NameOpts,
+    opts: &TargetOpts,
+) -> std::io::Result<Option<(matcher::Matcher, std::path::PathBuf)>> {
+    let path = match file_gen(vec![], opts, false) {
+        Ok(file_gen) => file_gen.get_target_path()?,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(None);
+        }
+    };
+    let root = rules_root(opts, &path)?;
+    let lines = if names.is_empty() {
+        match matcher::read_file(&path, matcher::Matcher::FILENAME) {
+            Ok(lines) => lines,
+            Err(e) => {
+                eprintln!("Unable to read `{}`: {e}. Aborting...", path.display());
+                return Ok(None);
+            }
+        }
+    } else {
+        let asset_db = AssetDB::new_decorated();
+        let assets = match resolve_names(&asset_db, names, name_opts) {
+            Some(assets) if !assets.is_empty() => assets,
+            _ => {
+                eprintln!("No `.gitignore` files to test against. Aborting...");
+                return Ok(None);
+            }
+        };
+        let mut lines = vec![];
+        for asset in assets.iter() {
+            if debug {
+                eprintln!("[DEBUG] testing against {asset}");
+            }
+            lines.extend(pattern::parse_asset(asset)?);
+        }
+        lines
+    };
+    let mut matcher = matcher::Matcher::new();
+    matcher.add("", lines);
+    add_gitignore_files(&mut matcher, opts, &root)?;
+    Ok(Some((matcher, root)))
+}
+
+fn handle_show(
+    debug: bool,
+    names: &[String],
+    name_opts: &NameOpts,
+    pager: bool,
+) -> std::io::Result<()> {
+    if names.is_empty() {
+        eprintln!("No names provided. Aborting...");
+        return Ok(());
+    }
+    let asset_db = AssetDB::new_decorated();
+    let mut buf = String::new();
+    let assets = match resolve_names(&asset_db, names, name_opts) {
+        Some(assets) => assets,
+        None => return Ok(()),
+    };
+    for asset in assets {
+        if debug {
+            eprintln!("[DEBUG] showing {asset}");
+        }
+        buf.push_str(&FileGen::render_section(&asset)?);
+    }
+    if buf.is_empty() {
+        eprintln!("Aborting...");
+        return Ok(());
+    }
+    if !(pager && page(&buf)) {
+        print_stdout(&buf)?;
+    }
+    Ok(())
+}
+
+/// Pipes the given text into the pager named by the `PAGER` environment
+/// variable (defaulting to `less`), returning whether the pager could
+/// be run.
+fn page(text: &str) -> bool {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| String::from("less"));
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // the pager may be quit before reading everything
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            child.wait().is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+/// Writes the given text to stdout, treating a closed pipe (e.g., when
+/// piped into `head`) as success.
+fn print_stdout(text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    match std::io::stdout().lock().write_all(text.as_bytes()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+fn handle_list(flag: Option<FlagArg>, grep: Option<&str>, show_terms: bool) {
+    let asset_db = AssetDB::new_decorated();
+    let grep = grep.map(|s| s.to_lowercase());
+    let mut assets = asset_db
+        .assets()
+        .iter()
+        .filter(|(_, asset)| flag.is_none_or(|flag| flag.matches(asset.git_ignore().get_flag())))
+        .filter(|(asset_name, _)| {
+            grep.as_ref()
+                .is_none_or(|s| asset_name.as_str().to_lowercase().contains(s))
+        })
+        .collect::<Vec<_>>();
+    assets.sort_by(|(a_name, a), (b_name, b)| {
+        a.git_ignore()
+            .get_flag()
+            .cmp(b.git_ignore().get_flag())
+            .then_with(|| {
+                a_name
+                    .as_str()
+                    .to_lowercase()
+                    .cmp(&b_name.as_str().to_lowercase())
+            })
+    });
+    let mut current = None;
+    for (asset_name, asset) in assets {
+        let asset_flag = asset.git_ignore().get_flag();
+        if current != Some(asset_flag) {
+            println!("[{asset_flag}]");
+            current = Some(asset_flag);
+        }
+        if show_terms {
+            let terms = asset
+                .terms_iter()
+                .map(|term| term.as_str())
+                .collect::<Vec<_>>();
+            println!("    {} <~ {}", asset_name.as_str(), terms.join(", "));
+        } else {
+            println!("    {}", asset_name.as_str());
+        }
+    }
+}
+
+/// Resolves each of the given names to an asset (see
+/// `AssetDB::resolve_name`), reporting every name that does not resolve
+/// to exactly one asset. Names that are ambiguous are resolved to the
+/// first candidate, while names that are not found are skipped, unless
+/// in strict mode, in which case `None` is returned if any name does
+/// not resolve.
+fn resolve_names(asset_db: &AssetDB, names: &[String], name_opts: &NameOpts) -> Option<Vec<Asset>> {
+    let resolutions = names
+        .iter()
+        .map(|name| (name, asset_db.resolve_name(name, name_opts.fuzzy)))
+        .collect::<Vec<_>>();
+    let unresolved = resolutions.iter().filter(|(_, r)| !r.is_resolved()).count();
+    if resolutions
+        .iter()
+        .any(|(_, r)| !matches!(r, assets::Resolution::Matched(_)))
+    {
+        eprintln!("Resolved the provided names as follows: ");
+        for (name, resolution) in resolutions.iter() {
+            eprintln!("    {name}: {resolution}");
+        }
+    }
+    if unresolved > 0 {
+        if name_opts.strict {
+            eprintln!("{unresolved} name(s) could not be resolved in strict mode. Aborting...");
+            return None;
+        }
+        eprintln!(
+            "Warning: {unresolved} name(s) could not be resolved to exactly one `.gitignore` file"
+        );
+    }
+    Some(
+        resolutions
+            .iter()
+            .filter_map(|(_, r)| r.asset().cloned())
+            .collect(),
+    )
+}
+
+/// Prints the contents that would be written according to the given
+/// plan to stdout, and a summary of what would happen to stderr.
+fn report_dry_run(plan: &Plan) -> std::io::Result<()> {
+    print_stdout(&plan.contents)?;
+    eprintln!(
+        "Dry run: {} bytes would be written to `{}`",
+        plan.contents.len(),
+        plan.path.display()
+    );
+    if let Some(backup) = plan.backup.as_ref() {
+        eprintln!(
+            "Dry run: `{}` would be backed up to `{}`",
+            plan.path.display(),
+            backup.display()
+        );
+    } else if plan.existing.is_some() {
+        eprintln!(
+            "Dry run: only the managed sections of `{}` would be regenerated",
+            plan.path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Prints a unified diff between the existing contents of the output
+/// file and those that would be written according to the given plan to
+/// stdout, either for the whole file or separately for each managed
+/// section (ignoring any text outside of them). Exits with status
+/// `EXIT_DIFFERENT` if there are any differences.
+fn report_diff(plan: &Plan, by_section: bool) -> std::io::Result<ExitCode> {
+    let path = plan.path.display().to_string();
+    let existing = plan.existing.as_deref().unwrap_or_default();
+    let old_label = match plan.existing {
+        Some(_) => path.clone(),
+        None => String::from("/dev/null"),
+    };
+    let new_label = format!("{path} (generated)");
+    let mut buf = String::new();
+    if by_section {
+        let adopted = managed::adopt_legacy(existing);
+        let old_sections = managed::sections(adopted.as_deref().unwrap_or(existing));
+        let new_sections = managed::sections(&plan.contents);
+        let removed = old_sections
+            .iter()
+            .filter(|old| new_sections.iter().all(|new| new.name != old.name))
+            .map(|old| (old.name, old.body, ""));
+        let sections = new_sections
+            .iter()
+            .map(|new| {
+                let old = old_sections.iter().find(|old| old.name == new.name);
+                (new.name, old.map_or("", |old| old.body), new.body)
+            })
+            .chain(removed);
+        for (name, old, new) in sections {
+            let old_label = format!("{old_label} [{name}]");
+            let new_label = format!("{new_label} [{name}]");
+            if let Some(diff) = diff::unified(old, new, &old_label, &new_label) {
+                buf.push_str(&diff);
+            }
+        }
+    } else if let Some(diff) = diff::unified(existing, &plan.contents, &old_label, &new_label) {
+        buf.push_str(&diff);
+    }
+    if buf.is_empty() {
+        eprintln!("No differences from `{path}`");
+        return Ok(ExitCode::SUCCESS);
+    }
+    print_stdout(&buf)?;
+    Ok(ExitCode::from(EXIT_DIFFERENT))
+}
+
+/// Prints the given status message, to stderr if stdout is reserved for
+/// the rendered content or a diff.
+fn print_status(opts: &GenOpts, msg: &str) {
+    if opts.dry_run || opts.wants_diff() {
+        eprintln!("{msg}");
+    } else {
+        println!("{msg}");
+    }
+}
+
+/// Warns about the files tracked by the git repository containing the
+/// output file of the given plan that the generated sections of its
+/// contents would ignore, taking into account the `.gitignore` files
+/// nested below the output directory.
+fn warn_tracked(plan: &Plan, opts: &GenOpts) -> std::io::Result<()> {
+    let (matcher, root) = plan_matcher(plan, &opts.target)?;
+    let repo = match git::Repo::discover(&root) {
+        Ok(Some(repo)) => repo,
+        Ok(None) => {
+            print_status(
+                opts,
+                &format!(
+                    "`{}` is not in a git repository, skipping the check for tracked files",
+                    root.display()
+                ),
+            );
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!(
+                "Unable to find the git repository: {e}. Skipping the check for tracked files"
+            );
+            return Ok(());
+        }
+    };
+    let tracked = match repo.tracked_files() {
+        Ok(tracked) => tracked,
+        Err(e) => {
+            eprintln!("Unable to read the git index: {e}. Skipping the check for tracked files");
+            return Ok(());
+        }
+    };
+    let base = match root.strip_prefix(&repo.work_tree) {
+        Ok(base) => base,
+        Err(_) => {
+            eprintln!(
+                "`{}` is not inside the work tree `{}`. Skipping the check for tracked files",
+                root.display(),
+                repo.work_tree.display()
+            );
+            return Ok(());
+        }
+    };
+    let verdicts = tracked
+        .iter()
+        .filter_map(|path| path.strip_prefix(base).ok())
+        .map(|rel| matcher.check(rel, false))
+        // files ignored by rules written by hand were already ignored
+        .filter(|verdict| {
+            verdict.ignored
+                && verdict
+                    .rule
+                    .is_some_and(|line| line.span.source.is_template())
+        })
+        .collect::<Vec<_>>();
+    if verdicts.is_empty() {
+        return Ok(());
+    }
+    print_status(
+        opts,
+        &format!(
+            "Warning: {} tracked file(s) would be ignored by `{}`: ",
+            verdicts.len(),
+            plan.path.display()
+        ),
     );
+    for verdict in verdicts {
+        print_status(opts, &format!("    {verdict}"));
+    }
     Ok(())
 }
+
+/// Lists the existing files and directories in the output directory of
+/// the given plan that the generated sections of its contents would
+/// ignore, followed by how many of them each rule and each `.gitignore`
+/// file accounts for.
+fn preview_ignored(plan: &Plan, opts: &GenOpts) -> std::io::Result<()> {
+    let (matcher, root) = plan_matcher(plan, &opts.target)?;
+    let ignored = matcher
+        .ignored_below(&root)?
+        .into_iter()
+        .filter_map(|(verdict, is_dir)| {
+            let line = verdict.rule.filter(|line| line.span.source.is_template())?;
+            Some((verdict.path, is_dir, line))
+        })
+        .collect::<Vec<_>>();
+    if ignored.is_empty() {
+        print_status(
+            opts,
+            &format!(
+                "Nothing in `{}` would be ignored by the generated content",
+                root.display()
+            ),
+        );
+        return Ok(());
+    }
+    print_status(
+        opts,
+        &format!(
+            "{} existing path(s) in `{}` would be ignored by the generated content: ",
+            ignored.len(),
+            root.display()
+        ),
+    );
+    // lines within managed sections are attributed to their templates
+    // (see `pattern::parse_file`), so counting by source counts by
+    // section, while lines outside of them were filtered out above
+    let mut by_rule: Vec<(&pattern::Line, usize)> = vec![];
+    let mut by_template: Vec<(&pattern::Source, usize)> = vec![];
+    for (path, is_dir, line) in ignored.iter() {
+        print_status(
+            opts,
+            &format!(
+                "    {}{}: ignored by `{}` ({})",
+                path.display(),
+                if *is_dir { "/" } else { "" },
+                line.text.trim_end(),
+                line.span
+            ),
+        );
+        // the matcher hands out the same line through distinct copies
+        match by_rule.iter_mut().find(|(l, _)| l.span == line.span) {
+            Some((_, count)) => *count += 1,
+            None => by_rule.push((line, 1)),
+        }
+        let source = line.span.source.as_ref();
+        match by_template.iter_mut().find(|(s, _)| *s == source) {
+            Some((_, count)) => *count += 1,
+            None => by_template.push((source, 1)),
+        }
+    }
+    print_status(opts, "By rule: ");
+    for (line, count) in by_rule {
+        print_status(
+            opts,
+            &format!("    `{}` ({}): {count}", line.text.trim_end(), line.span),
+        );
+    }
+    print_status(opts, "By `.gitignore` file: ");
+    for (source, count) in by_template {
+        print_status(opts, &format!("    {source}: {count}"));
+    }
+    Ok(())
+}
+
+/// Returns a matcher for the contents of the given plan along with the
+/// `.gitignore` files taking precedence over them (see
+/// `add_gitignore_files`), as well as the (canonical) directory the
+/// rules apply to.
+fn plan_matcher(
+    plan: &Plan,
+    opts: &TargetOpts,
+) -> std::io::Result<(matcher::Matcher, std::path::PathBuf)> {
+    let root = rules_root(opts, &plan.path)?;
+    let name = plan.path.file_name().unwrap_or_default();
+    let source = std::rc::Rc::new(pattern::Source::file(name));
+    let mut matcher = matcher::Matcher::new();
+    matcher.add("", pattern::parse_file(&plan.contents, &source));
+    add_gitignore_files(&mut matcher, opts, &root)?;
+    Ok((matcher, root))
+}
+
+/// Creates the `FileGen` for the given assets, writing to the output
+/// location described by the given options and removing repeated rules
+/// if `dedupe` is set.
+fn file_gen(assets: Vec<Asset>, opts: &TargetOpts, dedupe: bool) -> std::io::Result<FileGen> {
+    let mut file_gen = if opts.exclude {
+        let path = repo(opts)?.exclude_file()?;
+        // `info/` is created by `git init`, but isn't required
+        FileGen::with_assets(assets)
+            .with_created_target(path.parent().unwrap_or(&path))
+            .with_filename(path.file_name().unwrap_or_default())?
+            .preserving_existing()
+    } else {
+        match opts.dir.as_ref() {
+            Some(dir) => FileGen::with_assets_and_target(assets, dir)?,
+            None => FileGen::with_assets(assets),
+        }
+    };
+    if dedupe {
+        file_gen = file_gen.deduplicating();
+    }
+    match opts.output.as_ref() {
+        Some(output) => file_gen.with_filename(output),
+        None => Ok(file_gen),
+    }
+}
+
+/// Returns the git repository containing the output directory described
+/// by the given options.
+fn repo(opts: &TargetOpts) -> std::io::Result<git::Repo> {
+    let dir = match opts.dir.as_ref() {
+        Some(dir) => std::fs::canonicalize(dir)?,
+        None => std::env::current_dir()?,
+    };
+    match git::Repo::discover(&dir)? {
+        Some(repo) => Ok(repo),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("`{}` is not in a git repository", dir.display()),
+        )),
+    }
+}
+
+/// Returns the (canonical) directory the rules of the output file at
+/// `path` apply to, which for `--exclude` is the top-level directory of
+/// the work tree.
+fn rules_root(opts: &TargetOpts, path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    if opts.exclude {
+        return Ok(repo(opts)?.work_tree);
+    }
+    match path.parent() {
+        Some(dir) => std::fs::canonicalize(dir),
+        None => std::env::current_dir(),
+    }
+}
+
+/// Adds to the given matcher for the output file the rules of the
+/// `.gitignore` files taking precedence over it, i.e., those nested
+/// below `root` and, for `--exclude`, the one in `root` itself.
+fn add_gitignore_files(
+    matcher: &mut matcher::Matcher,
+    opts: &TargetOpts,
+    root: &std::path::Path,
+) -> std::io::Result<()> {
+    let file = root.join(matcher::Matcher::FILENAME);
+    if opts.exclude && file.is_file() {
+        matcher.add("", matcher::read_file(&file, matcher::Matcher::FILENAME)?);
+    }
+    matcher.add_nested(root)
+}
+
+/// Returns the body each section with the given name would have if
+/// generated by `file_gen`, if it includes the corresponding template.
+fn expected_body(file_gen: &FileGen, name: &str) -> std::io::Result<Option<String>> {
+    Ok(file_gen
+        .section_bodies()?
+        .into_iter()
+        .find(|(asset, _)| asset.git_ignore().get_name() == name)
+        .map(|(_, body)| body))
+}
+
+/// Writes the sections of the given assets to the output file, also
+/// removing those of other templates if `prune` is set.
+fn generate(
+    debug: bool,
+    assets: Vec<Asset>,
+    prune: bool,
+    opts: &GenOpts,
+) -> std::io::Result<ExitCode> {
+    if debug {
+        eprintln!("[DEBUG] found `.gitignore` files for...");
+        for asset in assets.iter() {
+            eprintln!("    {asset}");
+        }
+    }
+    let file_gen = match file_gen(assets, &opts.target, opts.dedupe) {
+        Ok(file_gen) if prune => file_gen.pruning_sections(),
+        Ok(file_gen) => file_gen,
+        Err(e) => {
+            eprintln!("Invalid output location: {e}. Aborting...");
+            return Ok(ExitCode::from(EXIT_ABORTED));
+        }
+    };
+    let plan = file_gen.plan()?;
+    if opts.check_tracked {
+        warn_tracked(&plan, opts)?;
+    }
+    if opts.preview_ignored {
+        preview_ignored(&plan, opts)?;
+    }
+    if opts.wants_diff() {
+        return report_diff(&plan, opts.by_section);
+    }
+    if opts.dry_run {
+        report_dry_run(&plan)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let bytes_written = file_gen.write_plan(&plan)?;
+    println!(
+        "Success! {} bytes were written to `{}`",
+        bytes_written,
+        plan.path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
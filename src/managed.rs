@@ -0,0 +1,354 @@
+//! Managed sections of generated `.gitignore` files.
+//!
+//! Every template written by `FileGen` is wrapped in a pair of marker
+//! comments, e.g.,
+//!
+//! ```text
+//! # >>> ignore_me: begin [Rust.gitignore] (default) hash:5c0d1b5e2a3f4d6c
+//! # [Rust.gitignore] (default)
+//! /target/
+//! # <<< ignore_me: end [Rust.gitignore]
+//! ```
+//!
+//! where the hash is that of the section body (i.e., the lines between
+//! the header and the end marker). Everything outside of such pairs is
+//! left untouched when regenerating a file, so that rules added by
+//! hand survive.
+
+const BEGIN: &str = "# >>> ignore_me: begin [";
+const END: &str = "# <<< ignore_me: end [";
+
+/// Returns the 64-bit FNV-1a hash of the given text. This is used
+/// instead of `std::hash` as its output must be stable across builds.
+pub fn content_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Returns the line opening the section of the `.gitignore` file with
+/// the given name, flag (as displayed) and body.
+pub fn begin_marker(name: &str, flag: &str, body: &str) -> String {
+    format!("{BEGIN}{name}] ({flag}) hash:{:016x}", content_hash(body))
+}
+
+/// Returns the line closing the section of the `.gitignore` file with
+/// the given name.
+pub fn end_marker(name: &str) -> String {
+    format!("{END}{name}]")
+}
+
+/// A managed section of a generated file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// The file name of the template, e.g., `Rust.gitignore`.
+    pub name: &'a str,
+    /// The flag of the template as displayed, e.g., `default`.
+    pub flag: &'a str,
+    /// The hash recorded in the begin marker.
+    pub hash: u64,
+    /// The header following the begin marker, if present.
+    pub header: Option<&'a str>,
+    /// The lines between the header and the end marker, each followed
+    /// by a newline.
+    pub body: &'a str,
+    /// The full text of the section, from the start of the begin
+    /// marker up to and including the end of the end marker line.
+    pub text: &'a str,
+}
+
+impl Section<'_> {
+    /// Returns whether the body was modified since it was written,
+    /// i.e., whether its hash no longer matches the recorded one.
+    pub fn is_modified(&self) -> bool {
+        content_hash(self.body) != self.hash
+    }
+}
+
+/// A piece of a generated file: either text outside of any managed
+/// section, or a managed section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block<'a> {
+    Text(&'a str),
+    Section(Section<'a>),
+}
+
+/// Splits the given contents into blocks of unmanaged text and managed
+/// sections, such that concatenating the text of all blocks yields the
+/// original contents. A begin marker without a matching end marker
+/// (or that can't be parsed) is treated as unmanaged text.
+pub fn parse(contents: &str) -> Vec<Block<'_>> {
+    let lines = line_offsets(contents);
+    let mut blocks = vec![];
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        let (start, line) = lines[i];
+        let section = parse_begin(line).and_then(|(name, flag, hash)| {
+            let end = end_marker(name);
+            let j = (i + 1..lines.len()).find(|&j| lines[j].1.trim_end() == end)?;
+            Some((j, name, flag, hash))
+        });
+        let (j, name, flag, hash) = match section {
+            Some(section) => section,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        if text_start < start {
+            blocks.push(Block::Text(&contents[text_start..start]));
+        }
+        let (header, body_start) = match lines.get(i + 1) {
+            Some(&(offset, line)) if i + 1 < j && line.starts_with("# [") => {
+                (Some(line.trim_end()), offset + line.len())
+            }
+            _ => (None, start + line.len()),
+        };
+        let (end_start, end_line) = lines[j];
+        let end = end_start + end_line.len();
+        blocks.push(Block::Section(Section {
+            name,
+            flag,
+            hash,
+            header,
+            body: &contents[body_start..end_start],
+            text: &contents[start..end],
+        }));
+        text_start = end;
+        i = j + 1;
+    }
+    if text_start < contents.len() {
+        blocks.push(Block::Text(&contents[text_start..]));
+    }
+    blocks
+}
+
+/// Returns the managed sections in the given contents.
+pub fn sections(contents: &str) -> Vec<Section<'_>> {
+    parse(contents)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Section(section) => Some(section),
+            Block::Text(_) => None,
+        })
+        .collect()
+}
+
+/// Returns the names recorded in the begin markers in the given
+/// contents that have no matching end marker.
+pub fn unterminated(contents: &str) -> Vec<&str> {
+    parse(contents)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Text(text) => Some(text),
+            Block::Section(_) => None,
+        })
+        .flat_map(str::lines)
+        .filter_map(|line| Some(parse_begin(line)?.0))
+        .collect()
+}
+
+/// Wraps every legacy section in the given contents in markers, i.e.,
+/// every section introduced by a header of the form `# [Name]` or
+/// `# [Name] (flag)` but not enclosed in markers, as written before
+/// sections were managed. Returns `None` if there are no such
+/// sections.
+///
+/// Since legacy sections have no end marker, each is taken to extend
+/// up to the next header (or the end of the surrounding unmanaged
+/// text), excluding trailing blank lines. Any rules added by hand
+/// directly below a legacy section will thus be considered part of it.
+pub fn adopt_legacy(contents: &str) -> Option<String> {
+    let mut adopted = false;
+    let mut buf = String::new();
+    for block in parse(contents) {
+        let text = match block {
+            Block::Section(section) => {
+                buf.push_str(section.text);
+                continue;
+            }
+            Block::Text(text) => text,
+        };
+        let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+        let mut i = 0;
+        while i < lines.len() {
+            let (name, flag) = match parse_header(lines[i]) {
+                Some(header) => header,
+                None => {
+                    buf.push_str(lines[i]);
+                    i += 1;
+                    continue;
+                }
+            };
+            let next = (i + 1..lines.len())
+                .find(|&j| parse_header(lines[j]).is_some())
+                .unwrap_or(lines.len());
+            let mut end = next;
+            while end > i + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            let mut body = lines[i + 1..end].concat();
+            if !(body.is_empty() || body.ends_with('\n')) {
+                body.push('\n');
+            }
+            buf.push_str(&begin_marker(name, flag.unwrap_or("default"), &body));
+            buf.push('\n');
+            buf.push_str(lines[i].trim_end());
+            buf.push('\n');
+            buf.push_str(&body);
+            buf.push_str(&end_marker(name));
+            buf.push('\n');
+            buf.push_str(&lines[end..next].concat());
+            adopted = true;
+            i = next;
+        }
+    }
+    adopted.then_some(buf)
+}
+
+/// Parses a section header of the form `# [Name]` or `# [Name] (flag)`
+/// into the name and flag it contains. As headers are only ever written
+/// for `.gitignore` files, comments that merely look similar (e.g.,
+/// `# [TODO] clean up`) are rejected.
+pub fn parse_header(line: &str) -> Option<(&str, Option<&str>)> {
+    let (name, rest) = line.trim_end().strip_prefix("# [")?.split_once(']')?;
+    if !name.ends_with(".gitignore") {
+        return None;
+    }
+    match rest
+        .strip_prefix(" (")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        Some(flag) if !flag.is_empty() => Some((name, Some(flag))),
+        _ if rest.is_empty() => Some((name, None)),
+        _ => None,
+    }
+}
+
+/// Parses a begin marker into the name, flag and hash it records.
+fn parse_begin(line: &str) -> Option<(&str, &str, u64)> {
+    let rest = line.trim_end().strip_prefix(BEGIN)?;
+    let (name, rest) = rest.split_once("] (")?;
+    let (flag, hash) = rest.rsplit_once(") hash:")?;
+    let hash = u64::from_str_radix(hash, 16).ok()?;
+    Some((name, flag, hash))
+}
+
+/// Returns every line of `contents` (including its line terminator)
+/// along with the byte offset it starts at.
+fn line_offsets(contents: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    contents
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trip() {
+        let body = "/target/\n**/*.rs.bk\n";
+        let contents = format!(
+            "# my rules\nsecret.txt\n\n{}\n# [Rust.gitignore] (default)\n{body}{}\n\nlocal/\n",
+            begin_marker("Rust.gitignore", "default", body),
+            end_marker("Rust.gitignore"),
+        );
+        let blocks = parse(&contents);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0], Block::Text("# my rules\nsecret.txt\n\n"));
+        match &blocks[1] {
+            Block::Section(section) => {
+                assert_eq!(section.name, "Rust.gitignore");
+                assert_eq!(section.flag, "default");
+                assert_eq!(section.header, Some("# [Rust.gitignore] (default)"));
+                assert_eq!(section.body, body);
+                assert!(!section.is_modified());
+            }
+            block => panic!("expected a section, found {block:?}"),
+        }
+        assert_eq!(blocks[2], Block::Text("\nlocal/\n"));
+        let text = blocks
+            .iter()
+            .map(|block| match block {
+                Block::Text(text) => *text,
+                Block::Section(section) => section.text,
+            })
+            .collect::<String>();
+        assert_eq!(text, contents);
+    }
+
+    #[test]
+    fn test_unterminated_section_is_text() {
+        let contents = format!(
+            "{}\n# [C.gitignore] (default)\n*.o\n",
+            begin_marker("C.gitignore", "default", "*.o\n")
+        );
+        assert_eq!(parse(&contents), vec![Block::Text(&contents)]);
+        assert_eq!(unterminated(&contents), vec!["C.gitignore"]);
+    }
+
+    #[test]
+    fn test_adopt_legacy() {
+        let contents =
+            "# GENERATED\n\n# [C.gitignore]\n*.o\n\n\n# [Vim.gitignore] (Global)\n*.swp\n";
+        let adopted = adopt_legacy(contents).unwrap();
+        let sections = sections(&adopted);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(
+            (sections[0].name, sections[0].flag),
+            ("C.gitignore", "default")
+        );
+        assert_eq!(sections[0].body, "*.o\n");
+        assert_eq!(
+            (sections[1].name, sections[1].flag),
+            ("Vim.gitignore", "Global")
+        );
+        assert_eq!(sections[1].body, "*.swp\n");
+        assert!(adopted.starts_with("# GENERATED\n\n"));
+        assert_eq!(adopt_legacy(&adopted), None);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("# [C.gitignore]\n"),
+            Some(("C.gitignore", None))
+        );
+        assert_eq!(
+            parse_header("# [Vim.gitignore] (custom: ~/my (own) templates)"),
+            Some(("Vim.gitignore", Some("custom: ~/my (own) templates")))
+        );
+        assert_eq!(parse_header("# [TODO] clean up"), None);
+        assert_eq!(parse_header("# [TODO]"), None);
+        assert_eq!(parse_header("# [C.gitignore] (default) old"), None);
+        assert_eq!(parse_header("# [C.gitignore] ()"), None);
+
+        // a comment resembling a header is left alone
+        let contents = "# [C.gitignore]\n*.o\n\n# [TODO] clean up\n/tmp/\n";
+        let adopted = adopt_legacy(contents).unwrap();
+        let sections = sections(&adopted);
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].body, "*.o\n\n# [TODO] clean up\n/tmp/\n");
+        assert_eq!(adopt_legacy("# [TODO] clean up\n/tmp/\n"), None);
+    }
+
+    #[test]
+    fn test_modified_section() {
+        let contents = format!(
+            "{}\n# [C.gitignore] (default)\n*.o\n*.a\n{}\n",
+            begin_marker("C.gitignore", "default", "*.o\n"),
+            end_marker("C.gitignore")
+        );
+        let sections = sections(&contents);
+        assert_eq!(sections.len(), 1);
+        assert!(sections[0].is_modified());
+    }
+}
@@ -0,0 +1,482 @@
+//! Evaluation of paths against the rules of `.gitignore` files, as
+//! parsed by the `pattern` module, following the semantics of git:
+//!
+//! - the last rule matching a path decides whether it is ignored, with
+//!   the rules of a `.gitignore` file taking precedence over those of
+//!   the files in the directories above it;
+//! - a negated rule re-includes what an earlier rule ignored, except
+//!   that nothing inside an ignored directory can be re-included;
+//! - a rule ending with a slash only matches directories.
+
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::pattern::{self, Line, Rule, Segment, Source, Token};
+
+/// The rules of a `.gitignore` file, along with the directory (relative
+/// to the root of the `Matcher`) they apply to.
+#[derive(Clone, Debug)]
+struct RuleSet {
+    base: PathBuf,
+    lines: Vec<Line>,
+}
+
+/// Decides whether paths are ignored according to a root `.gitignore`
+/// file and (optionally) the `.gitignore` files nested below it.
+#[derive(Clone, Debug, Default)]
+pub struct Matcher {
+    sets: Vec<RuleSet>,
+}
+
+/// Whether a path is ignored, and why.
+#[derive(Clone, Debug)]
+pub struct Verdict<'a> {
+    /// The path the verdict is about, relative to the root.
+    pub path: PathBuf,
+    pub ignored: bool,
+    /// The last rule matching either the path or, if `parent` is set,
+    /// the directory it is contained in.
+    pub rule: Option<&'a Line>,
+    /// The ignored directory containing the path, if any.
+    pub parent: Option<PathBuf>,
+}
+
+impl fmt::Display for Verdict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match (self.rule, self.parent.as_ref()) {
+            (Some(line), Some(parent)) => write!(
+                f,
+                "{path}: ignored, as `{}/` is ignored by `{}` ({})",
+                parent.display(),
+                line.text.trim_end(),
+                line.span
+            ),
+            (Some(line), None) if self.ignored => write!(
+                f,
+                "{path}: ignored by `{}` ({})",
+                line.text.trim_end(),
+                line.span
+            ),
+            (Some(line), None) => write!(
+                f,
+                "{path}: not ignored, re-included by `{}` ({})",
+                line.text.trim_end(),
+                line.span
+            ),
+            (None, _) => write!(f, "{path}: not ignored"),
+        }
+    }
+}
+
+impl Matcher {
+    pub const FILENAME: &'static str = ".gitignore";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the rules of the `.gitignore` file in the directory `base`
+    /// (relative to the root). Rules must be added for directories
+    /// before those below them.
+    pub fn add(&mut self, base: impl Into<PathBuf>, lines: Vec<Line>) {
+        self.sets.push(RuleSet {
+            base: base.into(),
+            lines,
+        });
+    }
+
+    /// Adds the rules of every `.gitignore` file in the directories
+    /// below `root` (but not of the one in `root` itself), skipping
+    /// directories ignored by the rules added so far, just like git
+    /// does.
+    pub fn add_nested(&mut self, root: impl AsRef<Path>) -> io::Result<()> {
+        self.walk(root.as_ref(), Path::new(""))
+    }
+
+    fn walk(&mut self, root: &Path, dir: &Path) -> io::Result<()> {
+        let mut subdirs = vec![];
+        for entry in fs::read_dir(root.join(dir))? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && entry.file_name() != ".git" {
+                subdirs.push(dir.join(entry.file_name()));
+            }
+        }
+        subdirs.sort();
+        for subdir in subdirs {
+            if self.check(&subdir, true).ignored {
+                continue;
+            }
+            let file = root.join(&subdir).join(Self::FILENAME);
+            if file.is_file() {
+                let lines = read_file(&file, subdir.join(Self::FILENAME))?;
+                self.add(subdir.clone(), lines);
+            }
+            self.walk(root, &subdir)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the verdicts for the files and directories below `root`
+    /// that are ignored, in the order of a depth-first walk sorted by
+    /// name, without descending into ignored directories (or `.git`).
+    /// Each verdict comes with whether the path is a directory.
+    pub fn ignored_below(&self, root: impl AsRef<Path>) -> io::Result<Vec<(Verdict<'_>, bool)>> {
+        let mut ignored = vec![];
+        self.collect_ignored(root.as_ref(), Path::new(""), &mut ignored)?;
+        Ok(ignored)
+    }
+
+    fn collect_ignored<'a>(
+        &'a self,
+        root: &Path,
+        dir: &Path,
+        ignored: &mut Vec<(Verdict<'a>, bool)>,
+    ) -> io::Result<()> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(root.join(dir))? {
+            let entry = entry?;
+            if entry.file_name() != ".git" {
+                entries.push((dir.join(entry.file_name()), entry.file_type()?.is_dir()));
+            }
+        }
+        entries.sort();
+        for (path, is_dir) in entries {
+            let verdict = self.check(&path, is_dir);
+            if verdict.ignored {
+                ignored.push((verdict, is_dir));
+            } else if is_dir {
+                self.collect_ignored(root, &path, ignored)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the last rule matching the given path (relative to the
+    /// root) itself, regardless of its parent directories.
+    pub fn last_match(&self, path: &Path, is_dir: bool) -> Option<&Line> {
+        self.matches(path, is_dir).pop()
+    }
+
+    /// Returns every rule matching the given path (relative to the
+    /// root) itself in evaluation order, regardless of its parent
+    /// directories.
+    pub fn matches(&self, path: &Path, is_dir: bool) -> Vec<&Line> {
+        let mut matches = vec![];
+        for set in self.sets.iter() {
+            let rel = match path.strip_prefix(&set.base) {
+                Ok(rel) if !rel.as_os_str().is_empty() => rel,
+                _ => continue,
+            };
+            matches.extend(
+                set.lines
+                    .iter()
+                    .filter(|line| line.rule().is_some_and(|rule| is_match(rule, rel, is_dir))),
+            );
+        }
+        matches
+    }
+
+    /// Returns every rule matching any of the parent directories of the
+    /// given path (relative to the root) or the path itself, along with
+    /// what it matches, in the order the rules are evaluated in by
+    /// `Matcher::check`.
+    pub fn explain(&self, path: &Path, is_dir: bool) -> Vec<(PathBuf, &Line)> {
+        let mut matches = vec![];
+        let mut prefix = PathBuf::new();
+        let components = path.components().collect::<Vec<_>>();
+        for (i, component) in components.iter().enumerate() {
+            prefix.push(component);
+            let is_dir = is_dir || i + 1 < components.len();
+            for line in self.matches(&prefix, is_dir) {
+                matches.push((prefix.clone(), line));
+            }
+        }
+        matches
+    }
+
+    /// Decides whether the given path (relative to the root) is
+    /// ignored, which is the case if any of its parent directories or
+    /// else the path itself is ignored.
+    pub fn check(&self, path: &Path, is_dir: bool) -> Verdict<'_> {
+        let mut parent = PathBuf::new();
+        let components = path.components().collect::<Vec<_>>();
+        for component in components.iter().take(components.len().saturating_sub(1)) {
+            parent.push(component);
+            if let Some(line) = self.last_match(&parent, true) {
+                if line.rule().is_some_and(|rule| !rule.negated) {
+                    return Verdict {
+                        path: path.to_path_buf(),
+                        ignored: true,
+                        rule: Some(line),
+                        parent: Some(parent),
+                    };
+                }
+            }
+        }
+        let rule = self.last_match(path, is_dir);
+        Verdict {
+            path: path.to_path_buf(),
+            ignored: rule.and_then(Line::rule).is_some_and(|rule| !rule.negated),
+            rule,
+            parent: None,
+        }
+    }
+}
+
+/// Reads and parses the `.gitignore` file at `path`, attributing lines
+/// outside of managed sections to `name`.
+pub fn read_file(path: &Path, name: impl AsRef<Path>) -> io::Result<Vec<Line>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(pattern::parse_file(&contents, &Rc::new(Source::file(name))))
+}
+
+/// Returns the given path relative to `root`, with `.` and `..`
+/// components resolved, or `None` if it lies outside of `root`.
+pub fn relative_path(root: &Path, path: &Path) -> Option<PathBuf> {
+    let path = match path.strip_prefix(root) {
+        Ok(rest) => rest,
+        Err(_) if path.is_absolute() => return None,
+        Err(_) => path,
+    };
+    let mut rel = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => rel.push(name),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !rel.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(rel)
+}
+
+/// Returns whether the rule matches the given path, relative to the
+/// directory of the `.gitignore` file containing the rule.
+pub fn is_match(rule: &Rule, path: &Path, is_dir: bool) -> bool {
+    if rule.dir_only && !is_dir {
+        return false;
+    }
+    let names = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    let names = names.iter().map(|name| name.as_ref()).collect::<Vec<_>>();
+    if rule.anchored {
+        match_segments(&rule.segments, &names)
+    } else {
+        (0..names.len()).any(|i| match_segments(&rule.segments, &names[i..]))
+    }
+}
+
+fn match_segments(segments: &[Segment], names: &[&str]) -> bool {
+    match segments.split_first() {
+        None => names.is_empty(),
+        // a trailing `**` matches everything inside, but not the
+        // directory itself
+        Some((Segment::AnyDirs, [])) => !names.is_empty(),
+        Some((Segment::AnyDirs, rest)) => {
+            (0..=names.len()).any(|i| match_segments(rest, &names[i..]))
+        }
+        Some((Segment::Glob(tokens), rest)) => match names.split_first() {
+            Some((name, names)) => {
+                match_glob(tokens, &name.chars().collect::<Vec<_>>()) && match_segments(rest, names)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches the characters of a name against a glob. On a mismatch, the
+/// last star seen is made to swallow one more character and matching
+/// resumes after it, which keeps the time quadratic however many stars
+/// the glob has, since earlier stars never need to be revisited.
+fn match_glob(tokens: &[Token], chars: &[char]) -> bool {
+    let (mut t, mut c) = (0, 0);
+    // the token after the last star seen, and the character from which
+    // the rest of the glob is being tried
+    let mut resume = None;
+    while c < chars.len() {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                t += 1;
+                resume = Some((t, c));
+            }
+            Some(token) if match_char(token, chars[c]) => {
+                t += 1;
+                c += 1;
+            }
+            _ => match resume {
+                Some((star_t, star_c)) => {
+                    t = star_t;
+                    c = star_c + 1;
+                    resume = Some((t, c));
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[t..].iter().all(|token| *token == Token::Star)
+}
+
+fn match_char(token: &Token, c: char) -> bool {
+    match token {
+        Token::Char(expected) => c == *expected,
+        Token::AnyChar => true,
+        Token::Class { negated, ranges } => {
+            ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c)) != *negated
+        }
+        Token::Star => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn matcher(sets: &[(&str, &str)]) -> Matcher {
+        let mut matcher = Matcher::new();
+        for (base, contents) in sets {
+            let source = Rc::new(Source::file(Path::new(base).join(".gitignore")));
+            matcher.add(*base, pattern::parse(contents, &source));
+        }
+        matcher
+    }
+
+    fn ignored(matcher: &Matcher, path: &str) -> bool {
+        let is_dir = path.ends_with('/');
+        matcher
+            .check(Path::new(path.trim_end_matches('/')), is_dir)
+            .ignored
+    }
+
+    #[test]
+    fn test_globs() {
+        let m = matcher(&[("", "*.o\n/target/\ndoc/*.txt\n**/logs/**\n[abc]?.md\n")]);
+        assert!(ignored(&m, "a.o"));
+        assert!(ignored(&m, "src/deep/a.o"));
+        assert!(ignored(&m, "target/"));
+        assert!(!ignored(&m, "target"));
+        assert!(!ignored(&m, "src/target/"));
+        assert!(ignored(&m, "doc/notes.txt"));
+        assert!(!ignored(&m, "doc/sub/notes.txt"));
+        assert!(ignored(&m, "x/logs/today/a"));
+        assert!(!ignored(&m, "x/logs/"));
+        assert!(ignored(&m, "b1.md"));
+        assert!(!ignored(&m, "d1.md"));
+    }
+
+    #[test]
+    fn test_many_stars() {
+        let m = matcher(&[("", "a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*b\n*x*?y\n")]);
+        assert!(!ignored(&m, &"a".repeat(60)));
+        assert!(ignored(&m, &format!("{}b", "a".repeat(60))));
+        assert!(ignored(&m, "axxyzy"));
+        assert!(!ignored(&m, "xy"));
+        assert!(ignored(&m, "x_y"));
+    }
+
+    #[test]
+    fn test_named_classes() {
+        let m = matcher(&[("", "*[[:space:]]*\n[![:alnum:]]*.tmp\n")]);
+        assert!(ignored(&m, "a b"));
+        assert!(ignored(&m, "tab\there"));
+        assert!(!ignored(&m, "a:b"));
+        assert!(ignored(&m, "_x.tmp"));
+        assert!(!ignored(&m, "x.tmp"));
+    }
+
+    #[test]
+    fn test_negation_and_parents() {
+        let m = matcher(&[
+            ("", "*.log\n!keep.log\nbuild/\n!build/keep.txt\n"),
+            ("sub", "!*.log\n"),
+        ]);
+        assert!(ignored(&m, "a.log"));
+        assert!(!ignored(&m, "keep.log"));
+        assert!(!ignored(&m, "sub/a.log"));
+        let verdict = m.check(Path::new("build/keep.txt"), false);
+        assert!(verdict.ignored);
+        assert_eq!(verdict.parent, Some(PathBuf::from("build")));
+        assert_eq!(verdict.rule.unwrap().span.line, 3);
+    }
+
+    #[test]
+    fn test_explain() {
+        let m = matcher(&[
+            ("", "*.log\n!keep.log\nbuild/\n!build/keep.txt\n"),
+            ("sub", "!*.log\n"),
+        ]);
+        let explain = |path| {
+            m.explain(Path::new(path), false)
+                .into_iter()
+                .map(|(path, line)| (path, line.span.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            explain("build/keep.txt"),
+            vec![
+                (PathBuf::from("build"), ".gitignore:3".to_string()),
+                (PathBuf::from("build/keep.txt"), ".gitignore:4".to_string())
+            ]
+        );
+        // rules of nested files come after those of their parents
+        assert_eq!(
+            explain("sub/keep.log"),
+            vec![
+                (PathBuf::from("sub/keep.log"), ".gitignore:1".to_string()),
+                (PathBuf::from("sub/keep.log"), ".gitignore:2".to_string()),
+                (
+                    PathBuf::from("sub/keep.log"),
+                    "sub/.gitignore:1".to_string()
+                )
+            ]
+        );
+        assert!(explain("src/main.rs").is_empty());
+    }
+
+    #[test]
+    fn test_ignored_below() {
+        let dir = TempDir::new();
+        for path in ["lib/a.py", "lib/b.py", "src/a.pyc", "src/a.py"] {
+            dir.write(path, "");
+        }
+        let m = matcher(&[("", "lib/\n*.pyc\n")]);
+        let ignored = m
+            .ignored_below(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(verdict, is_dir)| (verdict.path, is_dir))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ignored,
+            vec![
+                (PathBuf::from("lib"), true),
+                (PathBuf::from("src/a.pyc"), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let root = Path::new("/repo");
+        assert_eq!(
+            relative_path(root, Path::new("/repo/src/./lib.rs")),
+            Some(PathBuf::from("src/lib.rs"))
+        );
+        assert_eq!(
+            relative_path(root, Path::new("src/../a")),
+            Some(PathBuf::from("a"))
+        );
+        assert_eq!(relative_path(root, Path::new("../a")), None);
+        assert_eq!(relative_path(root, Path::new("/elsewhere")), None);
+    }
+}
@@ -0,0 +1,619 @@
+//! Parsing of `.gitignore` content into typed rules.
+//!
+//! Every line is parsed into a `Line`, which records where it comes
+//! from (see `Span`) along with what it is: a blank line, a comment, a
+//! rule, or a pattern git would never match (see `PatternError`). The
+//! syntax follows `gitignore(5)`:
+//!
+//! - trailing spaces are ignored unless escaped with a backslash;
+//! - a leading `!` negates the rule, while a trailing `/` restricts it
+//!   to directories;
+//! - a pattern containing a slash other than a trailing one is anchored
+//!   to the directory of the `.gitignore` file, except that a leading
+//!   `**/` matches in all directories;
+//! - a `**` segment matches any number of directories, while `*`, `?`
+//!   and `[...]` match within a single path component;
+//! - a backslash escapes the character following it.
+
+use std::{fmt, io, path::Path, rc::Rc};
+
+use crate::{assets::Asset, managed};
+
+/// Where `.gitignore` content comes from: a template, or a file on disk.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Source {
+    /// The file name of the template or the path of the file.
+    pub name: String,
+    /// The flag of the template as displayed, e.g., `default`.
+    pub flag: Option<String>,
+}
+
+impl Source {
+    pub fn template(name: impl Into<String>, flag: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            flag: Some(flag.into()),
+        }
+    }
+
+    pub fn file(path: impl AsRef<Path>) -> Self {
+        Self {
+            name: path.as_ref().display().to_string(),
+            flag: None,
+        }
+    }
+
+    pub fn is_template(&self) -> bool {
+        self.flag.is_some()
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.flag.as_ref() {
+            Some(flag) => write!(f, "{} ({flag})", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// The location of a line: its source and (1-based) line number within
+/// that source.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub source: Rc<Source>,
+    pub line: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.source.name, self.line)?;
+        if let Some(flag) = self.source.flag.as_ref() {
+            write!(f, " ({flag})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A parsed line of `.gitignore` content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub span: Span,
+    /// The text of the line, without its line terminator.
+    pub text: String,
+    pub kind: Kind,
+}
+
+impl Line {
+    pub fn rule(&self) -> Option<&Rule> {
+        match &self.kind {
+            Kind::Rule(rule) => Some(rule),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Blank,
+    Comment,
+    Rule(Rule),
+    Invalid(PatternError),
+}
+
+/// Why a pattern can never match anything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternError {
+    /// The pattern ends with an unescaped backslash.
+    TrailingBackslash,
+    /// A `[` opens a character class that is never closed.
+    UnclosedClass,
+    /// A character class contains a `[:name:]` with an unknown name.
+    UnknownClassName,
+    /// The pattern consists only of slashes and/or a negation.
+    Empty,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::TrailingBackslash => write!(f, "the pattern ends with a backslash"),
+            PatternError::UnclosedClass => write!(f, "a `[` is never closed"),
+            PatternError::UnknownClassName => {
+                write!(f, "a `[:name:]` names an unknown character class")
+            }
+            PatternError::Empty => write!(f, "the pattern is empty"),
+        }
+    }
+}
+
+/// A rule of a `.gitignore` file.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rule {
+    /// Whether the rule re-includes what it matches (`!pattern`).
+    pub negated: bool,
+    /// Whether the rule only matches directories (`pattern/`).
+    pub dir_only: bool,
+    /// Whether the pattern must match starting at the directory of the
+    /// `.gitignore` file, as opposed to below any directory.
+    pub anchored: bool,
+    /// The slash-separated components of the pattern, never empty.
+    pub segments: Vec<Segment>,
+}
+
+/// A slash-separated component of a pattern.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// `**`, matching any number of path components.
+    AnyDirs,
+    /// A glob matching exactly one path component.
+    Glob(Vec<Token>),
+}
+
+/// An element of a glob.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Token {
+    Char(char),
+    /// `?`, matching any single character.
+    AnyChar,
+    /// `*`, matching any (possibly empty) sequence of characters.
+    Star,
+    /// `[...]`, matching any character in (or, if negated, outside of)
+    /// the given inclusive ranges. Classes such as `[:alpha:]` are
+    /// expanded into the ASCII ranges they stand for.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// Displays the rule in its normalized form, such that rules with the
+/// same normalized form behave identically, e.g., `/foo/bar` and
+/// `foo/bar` are both displayed as `foo/bar`, while `**/foo` is
+/// displayed as `foo`.
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pattern = String::new();
+        if self.anchored && self.segments.len() == 1 {
+            pattern.push('/');
+        } else if !self.anchored && self.segments.len() > 1 {
+            pattern.push_str("**/");
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                pattern.push('/');
+            }
+            match segment {
+                Segment::AnyDirs => pattern.push_str("**"),
+                Segment::Glob(tokens) => tokens.iter().for_each(|t| push_token(&mut pattern, t)),
+            }
+        }
+        if pattern.starts_with(['!', '#']) {
+            pattern.insert(0, '\\');
+        }
+        if pattern.ends_with(' ') {
+            pattern.pop();
+            pattern.push_str("\\ ");
+        }
+        if self.dir_only {
+            pattern.push('/');
+        }
+        if self.negated {
+            pattern.insert(0, '!');
+        }
+        write!(f, "{pattern}")
+    }
+}
+
+fn push_token(buf: &mut String, token: &Token) {
+    match token {
+        Token::Char(c) => {
+            if matches!(c, '*' | '?' | '[' | '\\' | '/') {
+                buf.push('\\');
+            }
+            buf.push(*c);
+        }
+        Token::AnyChar => buf.push('?'),
+        Token::Star => buf.push('*'),
+        Token::Class { negated, ranges } => {
+            buf.push('[');
+            if *negated {
+                buf.push('!');
+            }
+            for &(lo, hi) in ranges {
+                push_class_char(buf, lo);
+                if lo != hi {
+                    buf.push('-');
+                    push_class_char(buf, hi);
+                }
+            }
+            buf.push(']');
+        }
+    }
+}
+
+fn push_class_char(buf: &mut String, c: char) {
+    if matches!(c, '[' | ']' | '\\' | '-' | '!' | '^') {
+        buf.push('\\');
+    }
+    buf.push(c);
+}
+
+/// Parses the given `.gitignore` content, attributing every line to
+/// `source`.
+pub fn parse(contents: &str, source: &Rc<Source>) -> Vec<Line> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, text)| {
+            let span = Span {
+                source: source.clone(),
+                line: i + 1,
+            };
+            parse_line(text, span)
+        })
+        .collect()
+}
+
+/// Parses the contents of the given asset, attributing every line to
+/// its template.
+pub fn parse_asset(asset: &Asset) -> io::Result<Vec<Line>> {
+    let git_ignore = asset.git_ignore();
+    let source = Source::template(git_ignore.get_name(), git_ignore.get_flag().to_string());
+    Ok(parse(&asset.contents()?, &Rc::new(source)))
+}
+
+/// Parses the contents of a (possibly generated) `.gitignore` file,
+/// attributing the body of every managed section to its template (with
+/// line numbers relative to the template) and everything else to
+/// `file`.
+pub fn parse_file(contents: &str, file: &Rc<Source>) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut line = 1;
+    let push_text = |lines: &mut Vec<Line>, line: &mut usize, text: &str| {
+        for text in text.lines() {
+            let span = Span {
+                source: file.clone(),
+                line: *line,
+            };
+            lines.push(parse_line(text, span));
+            *line += 1;
+        }
+    };
+    for block in managed::parse(contents) {
+        let section = match block {
+            managed::Block::Text(text) => {
+                push_text(&mut lines, &mut line, text);
+                continue;
+            }
+            managed::Block::Section(section) => section,
+        };
+        // the body is a slice of the text of the section
+        let start = section.body.as_ptr() as usize - section.text.as_ptr() as usize;
+        let end = start + section.body.len();
+        push_text(&mut lines, &mut line, &section.text[..start]);
+        let source = Rc::new(Source::template(section.name, section.flag));
+        lines.extend(parse(section.body, &source));
+        line += section.body.lines().count();
+        push_text(&mut lines, &mut line, &section.text[end..]);
+    }
+    lines
+}
+
+/// Parses a single line of `.gitignore` content (without its line
+/// terminator).
+pub fn parse_line(text: &str, span: Span) -> Line {
+    let kind = match parse_pattern(text) {
+        Ok(Some(rule)) => Kind::Rule(rule),
+        Ok(None) if text.trim_end_matches(' ').is_empty() => Kind::Blank,
+        Ok(None) => Kind::Comment,
+        Err(e) => Kind::Invalid(e),
+    };
+    Line {
+        span,
+        text: text.to_string(),
+        kind,
+    }
+}
+
+/// Parses the pattern on the given line, returning `None` for blank and
+/// comment lines.
+pub fn parse_pattern(text: &str) -> Result<Option<Rule>, PatternError> {
+    let chars = trim_trailing_spaces(text);
+    let mut chars = &chars[..];
+    match chars.first() {
+        None | Some('#') => return Ok(None),
+        _ => (),
+    }
+    let negated = chars[0] == '!';
+    if negated {
+        chars = &chars[1..];
+    }
+    let mut segments = vec![];
+    let mut tokens = vec![];
+    let mut leading_slash = false;
+    let mut dir_only = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '/' => {
+                if i == 0 {
+                    leading_slash = true;
+                } else if i + 1 == chars.len() {
+                    dir_only = true;
+                }
+                if !tokens.is_empty() {
+                    segments.push(segment(std::mem::take(&mut tokens)));
+                }
+            }
+            '\\' => {
+                let c = *chars.get(i + 1).ok_or(PatternError::TrailingBackslash)?;
+                tokens.push(Token::Char(c));
+                i += 1;
+            }
+            '?' => tokens.push(Token::AnyChar),
+            '*' => tokens.push(Token::Star),
+            '[' => {
+                let (token, len) = parse_class(&chars[i..])?;
+                tokens.push(token);
+                i += len - 1;
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    if !tokens.is_empty() {
+        segments.push(segment(tokens));
+    }
+    if segments.is_empty() {
+        return Err(PatternError::Empty);
+    }
+    let mut anchored = leading_slash || segments.len() > 1;
+    if segments.len() > 1 && segments[0] == Segment::AnyDirs {
+        segments.remove(0);
+        anchored = false;
+    }
+    Ok(Some(Rule {
+        negated,
+        dir_only,
+        anchored,
+        segments,
+    }))
+}
+
+/// Returns the segment for the given tokens, which are `**` if they
+/// consist of exactly two stars. Otherwise, consecutive stars are
+/// equivalent to a single one.
+fn segment(mut tokens: Vec<Token>) -> Segment {
+    if tokens == [Token::Star, Token::Star] {
+        return Segment::AnyDirs;
+    }
+    tokens.dedup_by(|a, b| *a == Token::Star && *b == Token::Star);
+    Segment::Glob(tokens)
+}
+
+/// Parses the character class at the start of `chars`, returning it
+/// along with the number of characters it spans.
+fn parse_class(chars: &[char]) -> Result<(Token, usize), PatternError> {
+    let mut i = 1;
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = vec![];
+    let first = i;
+    loop {
+        let mut c = *chars.get(i).ok_or(PatternError::UnclosedClass)?;
+        if c == ']' && i > first {
+            return Ok((Token::Class { negated, ranges }, i + 1));
+        }
+        if c == '[' && chars.get(i + 1) == Some(&':') {
+            // like git, `[:` only starts a named class if the next `]`
+            // follows a `:`, and is a plain `[` otherwise
+            let end = (i + 2..chars.len())
+                .find(|&j| chars[j] == ']')
+                .ok_or(PatternError::UnclosedClass)?;
+            if end > i + 2 && chars[end - 1] == ':' {
+                let name = chars[i + 2..end - 1].iter().collect::<String>();
+                ranges.extend(named_class(&name).ok_or(PatternError::UnknownClassName)?);
+                i = end + 1;
+                continue;
+            }
+        }
+        if c == '\\' {
+            i += 1;
+            c = *chars.get(i).ok_or(PatternError::UnclosedClass)?;
+        }
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&hi)) if hi != ']' => {
+                let (hi, len) = match hi {
+                    '\\' => (*chars.get(i + 3).ok_or(PatternError::UnclosedClass)?, 4),
+                    hi => (hi, 3),
+                };
+                ranges.push((c, hi));
+                i += len;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Returns the ASCII ranges making up the character class with the
+/// given name, as in `[:alpha:]`, if git knows it.
+fn named_class(name: &str) -> Option<&'static [(char, char)]> {
+    Some(match name {
+        "alnum" => &[('0', '9'), ('A', 'Z'), ('a', 'z')],
+        "alpha" => &[('A', 'Z'), ('a', 'z')],
+        "blank" => &[('\t', '\t'), (' ', ' ')],
+        "cntrl" => &[('\0', '\x1f'), ('\x7f', '\x7f')],
+        "digit" => &[('0', '9')],
+        "graph" => &[('!', '~')],
+        "lower" => &[('a', 'z')],
+        "print" => &[(' ', '~')],
+        "punct" => &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+        "space" => &[('\t', '\r'), (' ', ' ')],
+        "upper" => &[('A', 'Z')],
+        "xdigit" => &[('0', '9'), ('A', 'F'), ('a', 'f')],
+        _ => return None,
+    })
+}
+
+/// Returns the characters of the given line without its trailing
+/// spaces, unless they are escaped with a backslash.
+fn trim_trailing_spaces(text: &str) -> Vec<char> {
+    let mut chars = text.chars().collect::<Vec<_>>();
+    while chars.last() == Some(&' ') {
+        let backslashes = chars[..chars.len() - 1]
+            .iter()
+            .rev()
+            .take_while(|&&c| c == '\\')
+            .count();
+        if backslashes % 2 == 1 {
+            break;
+        }
+        chars.pop();
+    }
+    chars
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(text: &str) -> Rule {
+        match parse_pattern(text) {
+            Ok(Some(rule)) => rule,
+            result => panic!("expected a rule for {text:?}, found {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let target = rule("/target/");
+        assert!(target.anchored && target.dir_only && !target.negated);
+        assert_eq!(
+            target.segments,
+            vec![Segment::Glob(vec![
+                Token::Char('t'),
+                Token::Char('a'),
+                Token::Char('r'),
+                Token::Char('g'),
+                Token::Char('e'),
+                Token::Char('t'),
+            ])]
+        );
+        let bk = rule("**/*.rs.bk");
+        assert!(!bk.anchored);
+        assert_eq!(bk.segments.len(), 1);
+        assert!(rule("doc/frotz").anchored);
+        assert!(!rule("frotz/").anchored);
+        let negated = rule("!foo/**/[a-c]?.txt");
+        assert!(negated.negated && negated.anchored);
+        assert_eq!(negated.segments[1], Segment::AnyDirs);
+        assert_eq!(
+            negated.segments[2],
+            Segment::Glob(vec![
+                Token::Class {
+                    negated: false,
+                    ranges: vec![('a', 'c')],
+                },
+                Token::AnyChar,
+                Token::Char('.'),
+                Token::Char('t'),
+                Token::Char('x'),
+                Token::Char('t'),
+            ])
+        );
+        assert_eq!(rule("\\#file\\ ").to_string(), "\\#file\\ ");
+        assert_eq!(rule("a/**/b/").to_string(), "a/**/b/");
+        assert_eq!(rule("**/foo").to_string(), "foo");
+        assert_eq!(rule("/foo/bar").to_string(), "foo/bar");
+        assert_eq!(rule("**/foo/bar").to_string(), "**/foo/bar");
+    }
+
+    #[test]
+    fn test_parse_named_classes() {
+        let class = |text| match &rule(text).segments[0] {
+            Segment::Glob(tokens) => tokens[0].clone(),
+            segment => panic!("expected a glob, found {segment:?}"),
+        };
+        assert_eq!(
+            class("[[:space:]]"),
+            Token::Class {
+                negated: false,
+                ranges: vec![('\t', '\r'), (' ', ' ')],
+            }
+        );
+        assert_eq!(
+            class("[!_[:alpha:]]"),
+            Token::Class {
+                negated: true,
+                ranges: vec![('_', '_'), ('A', 'Z'), ('a', 'z')],
+            }
+        );
+        // without a `:]`, `[:` is a plain `[` followed by a `:`
+        assert_eq!(
+            class("[[:]"),
+            Token::Class {
+                negated: false,
+                ranges: vec![('[', '['), (':', ':')],
+            }
+        );
+        assert_eq!(rule("[[:]").to_string(), "[\\[:]");
+        assert_eq!(rule("[\\[:]"), rule("[[:]"));
+        assert_eq!(
+            parse_pattern("[[:word:]]"),
+            Err(PatternError::UnknownClassName)
+        );
+        assert_eq!(
+            parse_pattern("[[:alpha:]"),
+            Err(PatternError::UnclosedClass)
+        );
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let source = Rc::new(Source::template("Test.gitignore", "default"));
+        let lines = parse("# comment\n\n*.o  \nfoo\\\n[abc\n!/\n", &source);
+        let kinds = lines.iter().map(|l| l.kind.clone()).collect::<Vec<_>>();
+        assert_eq!(kinds[0], Kind::Comment);
+        assert_eq!(kinds[1], Kind::Blank);
+        assert_eq!(rule("*.o"), lines[2].rule().unwrap().clone());
+        assert_eq!(kinds[3], Kind::Invalid(PatternError::TrailingBackslash));
+        assert_eq!(kinds[4], Kind::Invalid(PatternError::UnclosedClass));
+        assert_eq!(kinds[5], Kind::Invalid(PatternError::Empty));
+        assert_eq!(lines[2].span.line, 3);
+        assert_eq!(lines[2].span.to_string(), "Test.gitignore:3 (default)");
+    }
+
+    #[test]
+    fn test_parse_file_attributes_sections() {
+        let body = "/target/\n*.pdb\n";
+        let contents = format!(
+            "mine/\n{}\n# [Rust.gitignore] (default)\n{body}{}\nlocal/\n",
+            managed::begin_marker("Rust.gitignore", "default", body),
+            managed::end_marker("Rust.gitignore"),
+        );
+        let file = Rc::new(Source::file(".gitignore"));
+        let lines = parse_file(&contents, &file);
+        let spans = lines
+            .iter()
+            .map(|l| (l.span.source.name.as_str(), l.span.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![
+                (".gitignore", 1),
+                (".gitignore", 2),
+                (".gitignore", 3),
+                ("Rust.gitignore", 1),
+                ("Rust.gitignore", 2),
+                (".gitignore", 6),
+                (".gitignore", 7),
+            ]
+        );
+    }
+}
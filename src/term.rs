@@ -1,31 +1,30 @@
+//! Note that the `.gitignore` files in the static `assets` may not
+//! necessarily provide a one-to-one relationship between programming
+//! languages and respective `.gitignore` files.
+//!
+//! For example, while `Rust.gitignore` provides a simple bijective
+//! relationship between the `Rust` language and the corresponding
+//! `.gitignore` file, the same does not hold for `JavaScript`, which
+//! not only does not have a corresponding `.gitignore` file named
+//! after itself (i.e., there is no `JavaScript.gitignore`); this is
+//! because the language's ecosystem is not restricted to a single
+//! project manager! In fact, for `JavaScript` we have the options of
+//! `Node.gitignore` for standard `Node` projects, as well as
+//! `Yeoman.gitignore` for VSCode extensions.
+//!
+//! Thus, our solution is to build a small database of *terms* and
+//! their associated `.gitignore` files. These `Term`s will
+//! effectively be pointers to interned strings so that `String`s
+//! need not be allocated more than necessary. This also allows for
+//! faster comparison, as each `Term` contains a `u32`, which is
+//! cheaper to compare than `String`s alone.
+
 use std::{
     collections::HashMap,
     ffi::OsStr,
     sync::{Arc, Mutex},
 };
 
-///! Note that the `.gitignore` files in the static `assets` may not
-///! necessarily provide a one-to-one relationship between programming
-///! languages and respective `.gitignore` files.
-///!
-///! For example, while `Rust.gitignore` provides a simple bijective
-///! relationship between the `Rust` language and the corresponding
-///! `.gitignore` file, the same does not hold for `JavaScript`, which
-///! not only does not have a corresponding `.gitignore` file named
-///! after itself (i.e., there is no `JavaScript.gitignore`); this is
-///! because the language's ecosystem is not restricted to a single
-///! project manager! In fact, for `JavaScript` we have the options of
-///! `Node.gitignore` for standard `Node` projects, as well as
-///! `Yeoman.gitignore` for VSCode extensions.
-///!
-///! Thus, our solution is to build a small database of *terms* and
-///! their associated `.gitignore` files. These `Term`s will
-///! effectively be pointers to interned strings so that `String`s
-///! need not be allocated more than necessary. This also allows for
-///! faster comparison, as each `Term` contains a `u32`, which is
-///! cheaper to compare than `String`s alone.
-///!
-
 /// An interned `String` corresponding to text for which some
 /// information is stored regarding a corresponding `.gitignore` file.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -0,0 +1,505 @@
+//! Tests running the `ignore_me` binary in a temporary project, using
+//! project-local templates (see `AssetSource::local`) so that they
+//! don't depend on the templates embedded at build time or installed
+//! on the machine.
+
+#[allow(dead_code)]
+#[path = "../src/test_util.rs"]
+mod test_util;
+
+use std::{fs, path::Path, process::Command};
+
+use test_util::TempDir;
+
+/// The templates available to every project.
+const TEMPLATES: &[(&str, &str)] = &[("Alpha", "*.alpha\n/alpha-out/\n"), ("Beta", "*.beta\n")];
+
+/// A temporary project directory, which also serves as the home
+/// directory of the user running `ignore_me` in it.
+struct Project {
+    dir: TempDir,
+}
+
+/// The outcome of running `ignore_me`.
+struct Run {
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+impl Project {
+    fn new() -> Self {
+        let dir = TempDir::new();
+        for (name, contents) in TEMPLATES {
+            dir.write(format!(".ignore_me/templates/{name}.gitignore"), contents);
+        }
+        Self { dir }
+    }
+
+    fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    fn write(&self, path: &str, contents: &str) {
+        self.dir.write(path, contents);
+    }
+
+    fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.path().join(path)).unwrap()
+    }
+
+    fn run(&self, args: &[&str]) -> Run {
+        self.run_in("", args)
+    }
+
+    /// Makes the project a git repository tracking the given files by
+    /// writing its index (version 2, with
+    /// SHA-1 object names) by hand.
+    fn track(&self, paths: &[&str]) {
+        let mut index = b"DIRC".to_vec();
+        index.extend_from_slice(&2u32.to_be_bytes());
+        index.extend_from_slice(&(paths.len() as u32).to_be_bytes());
+        for path in paths {
+            let start = index.len();
+            // timestamps, device, inode, mode, uid, gid, size and
+            // object name, none of which are read
+            index.resize(start + 60, 0);
+            index.extend_from_slice(&(path.len() as u16).to_be_bytes());
+            index.extend_from_slice(path.as_bytes());
+            index.push(0);
+            while !(index.len() - start).is_multiple_of(8) {
+                index.push(0);
+            }
+        }
+        fs::create_dir_all(self.path().join(".git")).unwrap();
+        fs::write(self.path().join(".git/index"), index).unwrap();
+    }
+
+    /// Runs `ignore_me` in the given subdirectory of the project.
+    fn run_in(&self, dir: &str, args: &[&str]) -> Run {
+        let root = self.path();
+        let output = Command::new(env!("CARGO_BIN_EXE_ignore_me"))
+            .args(args)
+            .current_dir(root.join(dir))
+            .env("HOME", root)
+            .env("XDG_CONFIG_HOME", root.join(".config"))
+            .env("XDG_DATA_HOME", root.join(".local/share"))
+            .env("XDG_DATA_DIRS", root.join(".system"))
+            .env_remove("GIT_CONFIG_GLOBAL")
+            .output()
+            .unwrap();
+        Run {
+            code: output.status.code().unwrap(),
+            stdout: String::from_utf8(output.stdout).unwrap(),
+            stderr: String::from_utf8(output.stderr).unwrap(),
+        }
+    }
+}
+
+#[test]
+fn test_add_appends_missing_sections() {
+    let project = Project::new();
+    project.write(".gitignore", "/local-only\n");
+    let run = project.run(&["add", "Alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let contents = project.read(".gitignore");
+    assert!(contents.starts_with("/local-only\n\n"), "{contents}");
+    assert!(contents.contains("*.alpha\n"));
+
+    // present sections are skipped, and the file is neither backed up
+    // nor rewritten
+    let run = project.run(&["add", "Alpha", "Beta"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(
+        run.stdout.contains("Skipping `Alpha.gitignore`"),
+        "{}",
+        run.stdout
+    );
+    let appended = project.read(".gitignore");
+    assert!(appended.starts_with(&contents));
+    assert_eq!(appended.matches("*.alpha\n").count(), 1);
+    assert!(appended.ends_with("*.beta\n# <<< ignore_me: end [Beta.gitignore]\n\n"));
+    assert!(!project.path().join(".gitignore.backup_1").exists());
+
+    let run = project.run(&["add", "Beta"]);
+    assert!(run.stdout.contains("Nothing to add"), "{}", run.stdout);
+    assert_eq!(project.read(".gitignore"), appended);
+}
+
+#[test]
+fn test_list_filters_by_flag_and_name() {
+    let project = Project::new();
+    let run = project.run(&["list", "--flag", "custom", "--grep", "AL"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.stdout, "[custom: .ignore_me/templates]\n    Alpha\n");
+
+    // the filters combine, so no built-in template is listed
+    let run = project.run(&["list", "--flag", "default", "--grep", "beta"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(!run.stdout.contains("Beta"), "{}", run.stdout);
+}
+
+#[test]
+fn test_show_prints_without_writing() {
+    let project = Project::new();
+    let run = project.run(&["show", "beta", "Alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let beta = run.stdout.find("*.beta\n").unwrap();
+    let alpha = run.stdout.find("*.alpha\n/alpha-out/\n").unwrap();
+    assert!(beta < alpha, "{}", run.stdout);
+    assert!(run
+        .stdout
+        .contains("# [Beta.gitignore] (custom: .ignore_me/templates)\n"));
+    assert!(!project.path().join(".gitignore").exists());
+}
+
+#[test]
+fn test_dry_run_writes_nothing() {
+    let project = Project::new();
+    let run = project.run(&["for", "Alpha", "--dry-run"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    // only the generated content goes to stdout, so that it can be
+    // redirected
+    assert!(run.stdout.starts_with("# GENERATED BY"), "{}", run.stdout);
+    assert!(run.stdout.contains("*.alpha\n"));
+    assert!(!run.stdout.contains("Dry run"));
+    assert!(run.stderr.contains("Dry run: "), "{}", run.stderr);
+    assert!(!project.path().join(".gitignore").exists());
+
+    project.write(".gitignore", "/local-only\n");
+    let run = project.run(&["for", "Beta", "--stdout"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(run.stdout.contains("*.beta\n"));
+    assert_eq!(project.read(".gitignore"), "/local-only\n");
+    assert!(!project.path().join(".gitignore.backup_1").exists());
+}
+
+#[test]
+fn test_partial_failure_is_reported() {
+    let project = Project::new();
+    let run = project.run(&["for", "Alpha", "Nope", "--strict"]);
+    assert_eq!(run.code, 2);
+    assert!(
+        run.stderr.contains("Alpha: Alpha.gitignore"),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("Nope: not found"));
+    assert!(run.stderr.contains("in strict mode. Aborting..."));
+    assert!(!project.path().join(".gitignore").exists());
+
+    // without `--strict`, the names that resolve are used
+    let run = project.run(&["for", "Alpha", "Nope"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(run.stderr.contains("Nope: not found"), "{}", run.stderr);
+    assert!(run.stderr.contains("1 name(s) could not be resolved"));
+    assert!(project.read(".gitignore").contains("*.alpha\n"));
+}
+
+#[test]
+fn test_target_dir_and_output() {
+    let project = Project::new();
+    project.write("sub/keep", "");
+    let run = project.run(&["for", "Alpha", "--dir", "sub", "-o", "rules.ignore"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(project.read("sub/rules.ignore").contains("*.alpha\n"));
+    assert!(!project.path().join(".gitignore").exists());
+
+    // the output directory must exist
+    let run = project.run(&["for", "Alpha", "--dir", "missing"]);
+    assert_eq!(run.code, 2);
+    assert!(
+        run.stderr.contains("Invalid output location"),
+        "{}",
+        run.stderr
+    );
+    assert!(!project.path().join("missing").exists());
+}
+
+#[test]
+fn test_read_only_commands_help() {
+    let project = Project::new();
+    for command in ["check", "test", "explain", "lint"] {
+        let run = project.run(&[command, "--help"]);
+        assert_eq!(run.code, 0, "{}", run.stderr);
+        assert!(!run.stdout.contains("--exclude"), "{}", run.stdout);
+        assert!(!run.stdout.contains("writ"), "{}", run.stdout);
+    }
+}
+
+#[test]
+fn test_prune_removes_other_sections() {
+    let project = Project::new();
+    for command in ["add", "update"] {
+        let run = project.run(&[command, "--help"]);
+        assert_eq!(run.code, 0, "{}", run.stderr);
+        assert!(!run.stdout.contains("--prune"), "{}", run.stdout);
+    }
+
+    let run = project.run(&["for", "Alpha", "Beta"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let run = project.run(&["for", "Alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(project.read(".gitignore").contains("*.beta\n"));
+    let run = project.run(&["for", "--prune", "Alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let contents = project.read(".gitignore");
+    assert!(contents.contains("*.alpha\n"), "{contents}");
+    assert!(!contents.contains("Beta.gitignore"), "{contents}");
+}
+
+#[test]
+fn test_exclude_writes_to_the_repository() {
+    let project = Project::new();
+    project.write(".git/HEAD", "ref: refs/heads/main\n");
+    // project-local templates are looked up in the working directory
+    project.write("sub/.ignore_me/templates/Beta.gitignore", "*.beta\n");
+
+    // `info/` is created along with the file
+    let run = project.run(&["for", "Alpha", "--exclude"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let contents = project.read(".git/info/exclude");
+    assert!(contents.contains("*.alpha\n"), "{contents}");
+    assert!(!project.path().join(".gitignore").exists());
+
+    // the repository is found from below its top-level directory
+    let run = project.run_in("sub", &["add", "Beta", "--exclude"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let appended = project.read(".git/info/exclude");
+    assert!(appended.starts_with(&contents), "{appended}");
+    assert!(appended.contains("*.beta\n"), "{appended}");
+    assert!(!project.path().join("sub/.git").exists());
+
+    let outside = Project::new();
+    let run = outside.run(&["for", "Alpha", "--exclude"]);
+    assert_eq!(run.code, 2);
+    assert!(
+        run.stderr.contains("is not in a git repository"),
+        "{}",
+        run.stderr
+    );
+    assert!(!outside.path().join(".git").exists());
+}
+
+#[test]
+fn test_check_detects_drift() {
+    let project = Project::new();
+    let run = project.run(&["for", "Alpha", "Beta"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let run = project.run(&["check"]);
+    assert_eq!(run.code, 0, "{}", run.stdout);
+    assert!(run.stdout.contains("is up to date"));
+
+    // one template changes, the other section is edited by hand
+    project.write(".ignore_me/templates/Alpha.gitignore", "*.alpha\n");
+    let contents = project
+        .read(".gitignore")
+        .replace("*.beta\n", "*.beta\n/mine\n");
+    project.write(".gitignore", &contents);
+    let run = project.run(&["check"]);
+    assert_eq!(run.code, 1);
+    assert!(
+        run.stdout
+            .contains("Alpha.gitignore (custom: .ignore_me/templates): out of date"),
+        "{}",
+        run.stdout
+    );
+    assert!(run
+        .stdout
+        .contains("Beta.gitignore (custom: .ignore_me/templates): edited by hand"));
+    assert!(run.stdout.contains("2 problem(s) found"));
+    assert_eq!(project.read(".gitignore"), contents);
+}
+
+#[test]
+fn test_update_regenerates_sections_and_keeps_user_lines() {
+    let project = Project::new();
+    let run = project.run(&["for", "Alpha", "Beta"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let generated = project.read(".gitignore");
+
+    // one section is edited by hand, and rules are added around them
+    let edited = generated.replace("*.beta\n", "*.beta\n/mine\n");
+    project.write(".gitignore", &format!("/top\n{edited}/bottom\n"));
+    let run = project.run(&["update"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(
+        run.stdout.contains("Alpha.gitignore: up to date"),
+        "{}",
+        run.stdout
+    );
+    assert!(
+        run.stdout.contains("Beta.gitignore: updated"),
+        "{}",
+        run.stdout
+    );
+    let updated = project.read(".gitignore");
+    assert_eq!(updated, format!("/top\n{generated}/bottom\n"));
+
+    let run = project.run(&["update"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(
+        run.stdout.contains("is already up to date"),
+        "{}",
+        run.stdout
+    );
+    assert_eq!(project.read(".gitignore"), updated);
+}
+
+#[test]
+fn test_diff_by_section_shows_changed_sections_only() {
+    let project = Project::new();
+    let run = project.run(&["for", "Alpha", "Beta"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let contents = project.read(".gitignore");
+    project.write(".ignore_me/templates/Alpha.gitignore", "*.alpha\n");
+    let run = project.run(&["update", "--by-section"]);
+    assert_eq!(run.code, 1, "{}", run.stderr);
+    assert!(run.stdout.contains("[Alpha.gitignore]\n"), "{}", run.stdout);
+    assert!(!run.stdout.contains("Beta.gitignore"), "{}", run.stdout);
+    assert_eq!(run.stdout.matches("\n@@ ").count(), 1, "{}", run.stdout);
+    assert!(run.stdout.contains("\n-/alpha-out/\n"), "{}", run.stdout);
+    assert_eq!(project.read(".gitignore"), contents);
+}
+
+#[test]
+fn test_test_reports_each_path() {
+    let project = Project::new();
+    project.run(&["for", "Alpha"]);
+    let run = project.run(&["test", "src/main.rs"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.stdout, "src/main.rs: not ignored\n");
+
+    // any ignored path makes the status 1
+    let run = project.run(&["test", "out.alpha", "src/main.rs"]);
+    assert_eq!(run.code, 1, "{}", run.stderr);
+    let lines = run.stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{}", run.stdout);
+    assert!(
+        lines[0].starts_with("out.alpha: ignored by `*.alpha` (Alpha.gitignore:1"),
+        "{}",
+        run.stdout
+    );
+    assert_eq!(lines[1], "src/main.rs: not ignored");
+}
+
+#[test]
+fn test_explain_lists_matching_rules() {
+    let project = Project::new();
+    project.run(&["for", "Alpha"]);
+    let contents = project.read(".gitignore") + "!keep.alpha\n";
+    project.write(".gitignore", &contents);
+    let line = contents.lines().count();
+    let run = project.run(&["explain", "keep.alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let expected = format!(
+        "    1. `*.alpha` (Alpha.gitignore:1 (custom: .ignore_me/templates)) matches `keep.alpha`\n    \
+         2. `!keep.alpha` (.gitignore:{line}) matches `keep.alpha` <- decides\n\
+         keep.alpha: not ignored, re-included by `!keep.alpha` (.gitignore:{line})\n"
+    );
+    assert!(run.stdout.ends_with(&expected), "{}", run.stdout);
+
+    let run = project.run(&["explain", "alpha-out/x", "--with", "Beta,Alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(
+        run.stdout
+            .contains("alpha-out/x: ignored, as `alpha-out/` is ignored by `/alpha-out/`"),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn test_check_tracked_reads_the_index() {
+    let project = Project::new();
+    project.write("a.alpha", "");
+    project.write("sub/b.alpha", "");
+    project.write("sub/c.txt", "");
+    project.write("untracked.alpha", "");
+    project.track(&["a.alpha", "sub/b.alpha", "sub/c.txt"]);
+
+    let run = project.run(&["for", "Alpha", "--check-tracked", "--dry-run"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(
+        run.stderr
+            .contains("Warning: 2 tracked file(s) would be ignored"),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("    a.alpha: ignored by `*.alpha`"));
+    assert!(run.stderr.contains("    sub/b.alpha: ignored by `*.alpha`"));
+    assert!(!run.stderr.contains("untracked"));
+
+    // paths are relative to the output directory
+    let run = project.run(&[
+        "for",
+        "Alpha",
+        "--check-tracked",
+        "--dry-run",
+        "--dir",
+        "sub",
+    ]);
+    assert!(
+        run.stderr
+            .contains("Warning: 1 tracked file(s) would be ignored"),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("    b.alpha: ignored by `*.alpha`"));
+}
+
+#[test]
+fn test_check_tracked_skips_hand_written_rules() {
+    let project = Project::new();
+    project.write(".gitignore", "/local.txt\n");
+    project.write("sub/.gitignore", "*.txt\n");
+    project.track(&["a.alpha", "local.txt", "sub/c.txt"]);
+
+    // tracked files ignored by rules written by hand were ignored
+    // already, so only those of the generated sections are listed
+    let run = project.run(&["add", "Alpha", "--check-tracked", "--dry-run"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(
+        run.stderr
+            .contains("Warning: 1 tracked file(s) would be ignored"),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("    a.alpha: ignored by `*.alpha`"));
+    assert!(!run.stderr.contains(".txt"), "{}", run.stderr);
+}
+
+#[test]
+fn test_preview_ignored_counts_per_template() {
+    let project = Project::new();
+    project.write("a.alpha", "");
+    project.write("sub/b.alpha", "");
+    project.write("c.beta", "");
+    project.write(".gitignore", "/c.beta\n");
+    let run = project.run(&["add", "Alpha", "Beta", "--preview-ignored", "--diff"]);
+    assert_eq!(run.code, 1, "{}", run.stderr);
+    let custom = "(custom: .ignore_me/templates)";
+    let expected = format!(
+        "By rule: \n    \
+         `*.alpha` (Alpha.gitignore:1 {custom}): 2\n    \
+         `*.beta` (Beta.gitignore:1 {custom}): 1\n\
+         By `.gitignore` file: \n    \
+         Alpha.gitignore {custom}: 2\n    \
+         Beta.gitignore {custom}: 1\n"
+    );
+    assert!(run.stderr.ends_with(&expected), "{}", run.stderr);
+    assert!(run.stdout.contains("+*.beta\n"), "{}", run.stdout);
+}
+
+#[test]
+fn test_global_creates_the_default_file_when_writing() {
+    let project = Project::new();
+    let run = project.run(&["global", "Alpha", "--dry-run"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(run.stdout.contains("*.alpha\n"));
+    assert!(!project.path().join(".config").exists());
+
+    let run = project.run(&["global", "Alpha"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(project.read(".config/git/ignore").contains("*.alpha\n"));
+}